@@ -0,0 +1,84 @@
+// Auto-explore: walks the player towards the nearest place they haven't seen,
+// one step per turn, until something interesting happens
+
+use super::{RunState, State};
+use crate::components::{Player, Position};
+use crate::interrupt::Snapshot;
+use crate::map::Map;
+use crate::player::try_move_player;
+use crate::systems::awareness_system::VisibleMonsters;
+use rltk::{console, DijkstraMap};
+use specs::prelude::*;
+
+// how far the Dijkstra map will search for unexplored tiles
+const MAX_DEPTH: f32 = 2000.0;
+
+/// Takes one auto-explore step and runs the turn. Returns the state to carry
+/// on in: `AutoExplore` to keep going, or `Paused` when it should stop.
+pub fn auto_explore_turn(gs: &mut State) -> RunState {
+    if !gs.ecs.fetch::<VisibleMonsters>().seen.is_empty() {
+        console::log("You can't explore with monsters in view.");
+        return RunState::Paused;
+    }
+
+    let step = match next_step(&gs.ecs) {
+        Some(step) => step,
+        None => {
+            console::log("There's nowhere left to explore.");
+            return RunState::Paused;
+        }
+    };
+
+    let before = Snapshot::take(&gs.ecs);
+    try_move_player(step.0, step.1, &mut gs.ecs);
+    gs.run_systems();
+
+    if let Some(reason) = before.interruption(&gs.ecs) {
+        console::log(format!("You stop exploring: {}.", reason));
+        RunState::Paused
+    } else {
+        RunState::AutoExplore
+    }
+}
+
+/// Works out which way the player should step to get closer to the nearest
+/// unexplored tile they can reach, if there is one
+fn next_step(ecs: &World) -> Option<(i32, i32)> {
+    let map = ecs.fetch::<Map>();
+    let positions = ecs.read_storage::<Position>();
+    let players = ecs.read_storage::<Player>();
+    let (_player, pos) = (&players, &positions).join().next()?;
+
+    // The frontier is every tile the player has seen and could stand on that
+    // is next to one they haven't seen
+    let frontier: Vec<usize> = (0..map.tiles.len())
+        .filter(|i| {
+            let p = map.idx_point(*i);
+            map.revealed_tiles[*i]
+                && map.is_walkable(p.x, p.y)
+                && map
+                    .neighbours(p.x, p.y)
+                    .any(|(n, _)| !map.revealed_tiles[map.point_idx(n)])
+        })
+        .collect();
+    if frontier.is_empty() {
+        return None;
+    }
+
+    let player_idx = map.idx(pos.x, pos.y);
+    let dijkstra = DijkstraMap::new(map.width, map.height, &frontier, &*map, MAX_DEPTH);
+    if dijkstra.map[player_idx] == 0.0 || dijkstra.map[player_idx] >= MAX_DEPTH {
+        // already on the frontier without seeing any further, or it can't
+        // be reached
+        return None;
+    }
+
+    let next = DijkstraMap::find_lowest_exit(&dijkstra, player_idx, &*map)?;
+    if map.tiles[next].damage_on_enter().is_some() {
+        // don't walk into anything dangerous without being told to
+        return None;
+    }
+
+    let next = map.idx_point(next);
+    Some((next.x - pos.x, next.y - pos.y))
+}
@@ -0,0 +1,51 @@
+// A camera that keeps the player centered on the screen, so maps can be
+// larger than the terminal
+
+use rltk::Point;
+
+/// The window of the map that is drawn to the screen
+pub struct Camera {
+    // top-left corner of the view, in map coordinates
+    pub min_x: i32,
+    pub min_y: i32,
+    // size of the view, in screen cells
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Camera {
+    /// Creates a camera of the given size centered on `center`
+    pub fn new(center: Point, width: i32, height: i32) -> Camera {
+        Camera {
+            min_x: center.x - width / 2,
+            min_y: center.y - height / 2,
+            width,
+            height,
+        }
+    }
+
+    /// One past the right-most map column in view
+    pub fn max_x(&self) -> i32 {
+        self.min_x + self.width
+    }
+
+    /// One past the bottom-most map row in view
+    pub fn max_y(&self) -> i32 {
+        self.min_y + self.height
+    }
+
+    /// Translates a map position to a screen position, if it is in view
+    pub fn world_to_screen(&self, x: i32, y: i32) -> Option<(i32, i32)> {
+        let (sx, sy) = (x - self.min_x, y - self.min_y);
+        if sx >= 0 && sx < self.width && sy >= 0 && sy < self.height {
+            Some((sx, sy))
+        } else {
+            None
+        }
+    }
+
+    /// Translates a screen position to a map position
+    pub fn screen_to_world(&self, x: i32, y: i32) -> (i32, i32) {
+        (x + self.min_x, y + self.min_y)
+    }
+}
@@ -0,0 +1,134 @@
+// Resolving attacks with dice. An attack rolls a d20 and adds the attacker's
+// power; if that reaches the defender's armour class, the attack hits and
+// rolls its damage dice. A natural 20 always hits and rolls the damage dice
+// twice, and a natural 1 always misses.
+
+use rltk::{DiceType, RandomNumberGenerator};
+
+const CRITICAL_ROLL: i32 = 20;
+const FUMBLE_ROLL: i32 = 1;
+
+/// The armour class of something with the given defense
+pub fn armour_class(defense: i32) -> i32 {
+    10 + defense
+}
+
+/// How an attack turned out, with the damage it did
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum AttackOutcome {
+    Fumble,
+    Miss,
+    Hit(i32),
+    Critical(i32),
+}
+
+/// How a d20 roll turned out, before any damage is rolled
+#[derive(PartialEq, Copy, Clone, Debug)]
+enum RollResult {
+    Fumble,
+    Miss,
+    Hit,
+    Critical,
+}
+
+fn roll_result(natural: i32, attack_bonus: i32, armour_class: i32) -> RollResult {
+    if natural == CRITICAL_ROLL {
+        RollResult::Critical
+    } else if natural == FUMBLE_ROLL {
+        RollResult::Fumble
+    } else if natural + attack_bonus >= armour_class {
+        RollResult::Hit
+    } else {
+        RollResult::Miss
+    }
+}
+
+/// Rolls an attack against `armour_class`, and its damage if it hits
+pub fn roll_attack(
+    rng: &mut RandomNumberGenerator,
+    attack_bonus: i32,
+    armour_class: i32,
+    damage: DiceType,
+) -> AttackOutcome {
+    match roll_result(rng.roll_dice(1, 20), attack_bonus, armour_class) {
+        RollResult::Fumble => AttackOutcome::Fumble,
+        RollResult::Miss => AttackOutcome::Miss,
+        RollResult::Hit => AttackOutcome::Hit(roll_damage(rng, damage, 1)),
+        RollResult::Critical => AttackOutcome::Critical(roll_damage(rng, damage, 2)),
+    }
+}
+
+/// Rolls the damage dice `times` times, adding the bonus once. Damage is
+/// never negative.
+fn roll_damage(rng: &mut RandomNumberGenerator, damage: DiceType, times: i32) -> i32 {
+    let rolled = rng.roll_dice(damage.n_dice * times, damage.die_type);
+    i32::max(0, rolled + damage.bonus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// How many of the 20 faces of the d20 give each result, as
+    /// (fumbles, misses, hits, criticals)
+    fn chances(attack_bonus: i32, armour_class: i32) -> (i32, i32, i32, i32) {
+        let mut chances = (0, 0, 0, 0);
+        for natural in 1..=20 {
+            match roll_result(natural, attack_bonus, armour_class) {
+                RollResult::Fumble => chances.0 += 1,
+                RollResult::Miss => chances.1 += 1,
+                RollResult::Hit => chances.2 += 1,
+                RollResult::Critical => chances.3 += 1,
+            }
+        }
+        chances
+    }
+
+    #[test]
+    fn probability_table() {
+        // (attack bonus, armour class, expected chances out of 20)
+        let table = [
+            (0, 10, (1, 8, 10, 1)),
+            (5, 12, (1, 5, 13, 1)),
+            (4, 11, (1, 5, 13, 1)),
+            (0, 21, (1, 18, 0, 1)),  // only a critical can hit
+            (30, 10, (1, 0, 18, 1)), // only a fumble can miss
+        ];
+        for (attack_bonus, armour_class, expected) in table.iter() {
+            assert_eq!(
+                chances(*attack_bonus, *armour_class),
+                *expected,
+                "+{} against AC {}",
+                attack_bonus,
+                armour_class
+            );
+        }
+    }
+
+    #[test]
+    fn damage_stays_in_range() {
+        let mut rng = RandomNumberGenerator::seeded(42);
+        let dice = DiceType::new(1, 6, 2);
+        for _ in 0..1000 {
+            match roll_attack(&mut rng, 5, 12, dice) {
+                AttackOutcome::Hit(damage) => assert!((3..=8).contains(&damage)),
+                AttackOutcome::Critical(damage) => assert!((4..=14).contains(&damage)),
+                _ => {}
+            }
+        }
+
+        let weak = DiceType::new(1, 4, -5);
+        assert_eq!(roll_damage(&mut rng, weak, 1), 0);
+    }
+
+    #[test]
+    fn seeded_attacks_are_reproducible() {
+        let rolls = |seed| {
+            let mut rng = RandomNumberGenerator::seeded(seed);
+            (0..50)
+                .map(|_| roll_attack(&mut rng, 5, 12, DiceType::new(1, 6, 2)))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(rolls(7), rolls(7));
+    }
+}
@@ -1,4 +1,8 @@
-use rltk::RGB;
+use crate::factions::FactionId;
+use crate::fov::FovAlgorithm;
+use crate::items::ConsumableKind;
+use crate::spells::Spell;
+use rltk::{DiceType, Point, RGB};
 use specs::prelude::*;
 use specs_derive::*;
 
@@ -21,6 +25,16 @@ pub struct Renderable {
     pub glyph: u8,
     pub fg: RGB, // foreground color?
     pub bg: RGB, // background color?
+    pub render_order: RenderOrder,
+}
+
+/// Which things are drawn on top when several share a tile. Later variants
+/// are drawn over earlier ones.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug)]
+pub enum RenderOrder {
+    Item, // also corpses and traps
+    Creature,
+    Particle,
 }
 
 // Components with no data are called "tag" components.
@@ -30,6 +44,19 @@ pub struct Player {}
 #[derive(Component)]
 pub struct Monster {}
 
+/// Component for allies that stay within `leash` tiles of their leader
+#[derive(Component, Debug)]
+pub struct Follower {
+    pub leader: Entity,
+    pub leash: i32,
+}
+
+/// Component for the side a creature is on
+#[derive(Component, Debug, Copy, Clone)]
+pub struct Faction {
+    pub id: FactionId,
+}
+
 /*
 Adding limited visibility so specific entities can only see the parts of the
  map they've already seen,
@@ -39,6 +66,7 @@ pub struct Viewshed {
     pub visible_tiles: Vec<rltk::Point>,
     pub range: i32,
     pub dirty: bool,
+    pub algorithm: FovAlgorithm,
 }
 
 #[derive(Component, Debug)]
@@ -49,6 +77,15 @@ pub struct Name {
 #[derive(Component, Debug)]
 pub struct BlocksTile {}
 
+/// Tag for entities that can move through deep water
+#[derive(Component, Debug)]
+pub struct Swimmer {}
+
+/// Component for creatures that have just waded into shallow water, and
+/// lose their next turn to it
+#[derive(Component, Debug)]
+pub struct Wading {}
+
 #[derive(Component, Debug)]
 pub struct CombatStats {
     pub max_hp: i32,
@@ -68,25 +105,62 @@ impl CombatStats {
     }
 }
 
+/// Component for the dice an entity rolls for damage when it hits in mêlée
+#[derive(Component, Debug)]
+pub struct MeleeDamage {
+    pub dice: DiceType,
+}
+
 /// Component that gives an entity the ability to do mêlée damage
 #[derive(Component, Debug, Clone)]
 pub struct CanMelee {
     pub target: Entity,
 }
 
+/// The kinds of damage, which some things resist or are vulnerable to
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum DamageType {
+    Slashing,
+    Piercing,
+    Fire,
+    Poison,
+    Magic,
+    Starvation,
+}
+
+/// One hit of damage, and whatever dealt it
+#[derive(Debug, Clone)]
+pub struct Damage {
+    pub amount: i32,
+    pub kind: DamageType,
+    // None for things like starving, that nobody is to blame for
+    pub source: Option<Entity>,
+}
+
 /// Component that tracks the incoming damage suffered by an entity
 #[derive(Component, Debug)]
 pub struct SuffersDamage {
-    pub amount: Vec<i32>,
+    pub damage: Vec<Damage>,
 }
 
 impl SuffersDamage {
-    pub fn new_damage(store: &mut WriteStorage<SuffersDamage>, victim: Entity, amount: i32) {
+    pub fn new_damage(
+        store: &mut WriteStorage<SuffersDamage>,
+        victim: Entity,
+        amount: i32,
+        kind: DamageType,
+        source: Option<Entity>,
+    ) {
+        let damage = Damage {
+            amount,
+            kind,
+            source,
+        };
         if let Some(incoming_damage) = store.get_mut(victim) {
-            incoming_damage.amount.push(amount);
+            incoming_damage.damage.push(damage);
         } else {
             let damage = SuffersDamage {
-                amount: vec![amount],
+                damage: vec![damage],
             };
             store
                 .insert(victim, damage)
@@ -94,3 +168,230 @@ impl SuffersDamage {
         }
     }
 }
+
+/// Component for entities that take more or less damage of some kinds.
+/// Resisted damage is halved, damage an entity is vulnerable to is doubled,
+/// and immune entities take none.
+#[derive(Component, Debug, Default)]
+pub struct Resistances {
+    pub resists: Vec<DamageType>,
+    pub vulnerable_to: Vec<DamageType>,
+    pub immune_to: Vec<DamageType>,
+}
+
+/// Component that remembers who last hurt an entity, to name them if it
+/// dies
+#[derive(Component, Debug)]
+pub struct LastAttacker {
+    pub entity: Entity,
+}
+
+/// Component for entities that get stronger by killing things
+#[derive(Component, Debug)]
+pub struct Experience {
+    pub level: i32,
+    pub xp: i32,
+}
+
+/// Component for entities that are worth experience to whoever kills them
+#[derive(Component, Debug)]
+pub struct XpReward {
+    pub xp: i32,
+}
+
+/// Kinds of traps, and what they do when triggered
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum TrapKind {
+    Spike,    // damages whatever steps on it
+    Teleport, // sends whatever steps on it somewhere random
+    Alarm,    // alerts nearby monsters
+}
+
+/// A trap that is triggered when anything moves onto it. Traps that don't
+/// rearm are removed after they are triggered.
+#[derive(Component, Debug)]
+pub struct Trap {
+    pub kind: TrapKind,
+    pub rearms: bool,
+}
+
+/// Tag for entities that are not drawn until the player spots them
+#[derive(Component, Debug)]
+pub struct Hidden {}
+
+/// Tag for entities that moved this turn
+#[derive(Component, Debug)]
+pub struct EntityMoved {}
+
+/// Component for monsters that heard something and want to investigate
+#[derive(Component, Debug)]
+pub struct HeardNoise {
+    pub at: Point,
+}
+
+/// Component for entities that spent their turn searching for hidden things.
+/// It lasts for one turn.
+#[derive(Component, Debug)]
+pub struct Searching {}
+
+/// Component for entities that give off light, like a torch
+#[derive(Component, Debug)]
+pub struct LightSource {
+    pub radius: i32,
+    pub colour: RGB,
+}
+
+/// Component for entities that slowly heal on their own, 1 hp every
+/// `turns_per_hp` turns
+#[derive(Component, Debug)]
+pub struct HealthRegen {
+    pub turns_per_hp: i32,
+    // turns since the last hp was healed
+    pub counter: i32,
+}
+
+impl HealthRegen {
+    pub fn new(turns_per_hp: i32) -> HealthRegen {
+        HealthRegen {
+            turns_per_hp,
+            counter: 0,
+        }
+    }
+}
+
+/// How hungry an entity is, from least to most
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum HungerState {
+    WellFed,
+    Normal,
+    Hungry,
+    Starving,
+}
+
+impl HungerState {
+    /// How many turns of healing a regenerating entity gets for each turn
+    /// that passes. Hungry things don't heal.
+    pub fn regen_per_turn(self) -> i32 {
+        match self {
+            HungerState::WellFed => 2,
+            HungerState::Normal => 1,
+            HungerState::Hungry | HungerState::Starving => 0,
+        }
+    }
+}
+
+/// Component for entities that need to eat. Each turn `duration` goes down,
+/// and when it runs out the entity gets hungrier.
+#[derive(Component, Debug)]
+pub struct HungerClock {
+    pub state: HungerState,
+    pub duration: i32,
+}
+
+impl HungerClock {
+    /// How long an entity stays well fed after eating
+    pub const WELL_FED_TURNS: i32 = 200;
+
+    pub fn well_fed() -> HungerClock {
+        HungerClock {
+            state: HungerState::WellFed,
+            duration: HungerClock::WELL_FED_TURNS,
+        }
+    }
+}
+
+/// Tag for things that can be picked up
+#[derive(Component, Debug)]
+pub struct Item {}
+
+/// Component for items that someone is carrying, rather than lying on the
+/// map
+#[derive(Component, Debug)]
+pub struct InBackpack {
+    pub owner: Entity,
+}
+
+/// Component for how much gold an entity is carrying
+#[derive(Component, Debug)]
+pub struct Purse {
+    pub gold: i32,
+}
+
+/// Component for gold lying on the floor, which goes into the purse of
+/// whoever picks it up
+#[derive(Component, Debug)]
+pub struct GoldPile {
+    pub amount: i32,
+}
+
+/// Component for what an item is worth to a vendor, before markup
+#[derive(Component, Debug)]
+pub struct Value {
+    pub gold: i32,
+}
+
+/// Component for NPCs that buy and sell items. They sell for `markup`
+/// percent more than an item is worth, and buy for that much less.
+#[derive(Component, Debug)]
+pub struct Vendor {
+    pub markup: i32,
+}
+
+/// Component for potions and scrolls, which are used up when used
+#[derive(Component, Debug)]
+pub struct Consumable {
+    pub kind: ConsumableKind,
+}
+
+/// Component for items that can be eaten
+#[derive(Component, Debug)]
+pub struct ProvidesFood {}
+
+/// Component for particles, which disappear after `lifetime_ms` milliseconds
+#[derive(Component, Debug)]
+pub struct ParticleLifetime {
+    pub lifetime_ms: f32,
+}
+
+/// Component for entities that can cast spells. Mana comes back at 1 point
+/// every `turns_per_point` turns.
+#[derive(Component, Debug)]
+pub struct Mana {
+    pub max: i32,
+    pub current: i32,
+    pub turns_per_point: i32,
+    // turns since the last point came back
+    pub counter: i32,
+}
+
+impl Mana {
+    pub fn new(max: i32, turns_per_point: i32) -> Mana {
+        Mana {
+            max,
+            current: max,
+            turns_per_point,
+            counter: 0,
+        }
+    }
+}
+
+/// Component listing the spells an entity can cast
+#[derive(Component, Debug)]
+pub struct KnownSpells {
+    pub spells: Vec<Spell>,
+}
+
+/// Component for entities casting a spell this turn. Spells with a range
+/// have a target.
+#[derive(Component, Debug)]
+pub struct WantsToCastSpell {
+    pub spell: Spell,
+    pub target: Option<Point>,
+}
+
+/// Component for entities that are too confused to act, for `turns` more
+/// turns
+#[derive(Component, Debug)]
+pub struct Confusion {
+    pub turns: i32,
+}
@@ -0,0 +1,86 @@
+// Who is on whose side. Every creature belongs to a faction, and how it
+// treats another creature depends on how their factions get along.
+
+use std::collections::HashMap;
+
+/// The sides creatures can be on
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+pub enum FactionId {
+    Player,
+    Goblins,
+    Orcs,
+}
+
+/// How one faction treats another
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Reaction {
+    Hostile,
+    Neutral,
+    Friendly,
+}
+
+/// Resource holding how every pair of factions gets along. Factions are
+/// always friendly to themselves, and neutral to anyone not in the table.
+pub struct Reactions {
+    table: HashMap<(FactionId, FactionId), Reaction>,
+}
+
+impl Default for Reactions {
+    fn default() -> Reactions {
+        let mut reactions = Reactions {
+            table: HashMap::new(),
+        };
+        reactions.set(FactionId::Player, FactionId::Goblins, Reaction::Hostile);
+        reactions.set(FactionId::Player, FactionId::Orcs, Reaction::Hostile);
+        // an old grudge
+        reactions.set(FactionId::Goblins, FactionId::Orcs, Reaction::Hostile);
+        reactions
+    }
+}
+
+impl Reactions {
+    /// Sets how two factions treat each other, both ways round
+    pub fn set(&mut self, a: FactionId, b: FactionId, reaction: Reaction) {
+        self.table.insert((a, b), reaction);
+        self.table.insert((b, a), reaction);
+    }
+
+    pub fn reaction(&self, a: FactionId, b: FactionId) -> Reaction {
+        if a == b {
+            return Reaction::Friendly;
+        }
+        *self.table.get(&(a, b)).unwrap_or(&Reaction::Neutral)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reactions_are_symmetric_and_factions_like_themselves() {
+        let mut reactions = Reactions::default();
+        assert_eq!(
+            reactions.reaction(FactionId::Orcs, FactionId::Orcs),
+            Reaction::Friendly
+        );
+        assert_eq!(
+            reactions.reaction(FactionId::Orcs, FactionId::Goblins),
+            Reaction::Hostile
+        );
+        assert_eq!(
+            reactions.reaction(FactionId::Goblins, FactionId::Orcs),
+            Reaction::Hostile
+        );
+
+        reactions.set(FactionId::Goblins, FactionId::Player, Reaction::Friendly);
+        assert_eq!(
+            reactions.reaction(FactionId::Player, FactionId::Goblins),
+            Reaction::Friendly
+        );
+        assert_eq!(
+            reactions.reaction(FactionId::Player, FactionId::Orcs),
+            Reaction::Hostile
+        );
+    }
+}
@@ -0,0 +1,321 @@
+// Field of view algorithms. rltk's own field of view isn't symmetric: a
+// monster could see you while you couldn't see it. The algorithms here are.
+
+use crate::map::Map;
+use rltk::{BaseMap, Point};
+
+/// The ways an entity can work out what it can see
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum FovAlgorithm {
+    /// Shadowcasting that guarantees that if A can see B, B can see A
+    SymmetricShadowcasting,
+    /// A tile is visible if a clear line runs between it and the viewer in
+    /// either direction. Sees a little more than shadowcasting, around
+    /// pillars and corners, and is also symmetric, but only with itself:
+    /// a permissive viewer can see a shadowcasting one that can't see it
+    /// back, so it should only be used if every creature uses it.
+    #[allow(dead_code)] // nothing uses it while creatures use shadowcasting
+    Permissive,
+}
+
+impl FovAlgorithm {
+    /// Returns every tile on the map that can be seen from `origin` within
+    /// `range` tiles
+    pub fn field_of_view(self, origin: Point, range: i32, map: &Map) -> Vec<Point> {
+        match self {
+            FovAlgorithm::SymmetricShadowcasting => shadowcast(origin, range, map),
+            FovAlgorithm::Permissive => permissive(origin, range, map),
+        }
+    }
+}
+
+/// True if `p` is no further than `range` from `origin`, measured the same
+/// way in both directions
+fn in_range(origin: Point, p: Point, range: i32) -> bool {
+    let (dx, dy) = (p.x - origin.x, p.y - origin.y);
+    dx * dx + dy * dy <= range * range
+}
+
+/// Anything off the map blocks sight
+fn blocks_sight(map: &Map, p: Point) -> bool {
+    !map.contains_point(p) || map.is_opaque(map.point_idx(p))
+}
+
+// Symmetric shadowcasting, after Albert Ford's description at
+// https://www.albertford.com/shadowcasting/. The area around the viewer is
+// split into four quadrants, and each is scanned row by row moving away from
+// the viewer, narrowing the range of slopes that can still be seen as walls
+// cast shadows.
+
+/// A slope as an exact fraction, so that rounding can't break symmetry.
+/// The denominator is always positive.
+#[derive(Copy, Clone)]
+struct Slope {
+    num: i32,
+    den: i32,
+}
+
+/// One of the rows scanned in a quadrant, `depth` rows from the viewer
+#[derive(Copy, Clone)]
+struct Row {
+    depth: i32,
+    start: Slope,
+    end: Slope,
+}
+
+impl Row {
+    /// The first and last column of the row that fall between the slopes
+    fn columns(&self) -> (i32, i32) {
+        // round ties up for the start and down for the end
+        let min_col = floor_div(
+            2 * self.depth * self.start.num + self.start.den,
+            2 * self.start.den,
+        );
+        let max_col = -floor_div(
+            self.end.den - 2 * self.depth * self.end.num,
+            2 * self.end.den,
+        );
+        (min_col, max_col)
+    }
+
+    /// True if a floor tile in this column is visible from the viewer, and
+    /// so the viewer is visible from it
+    fn is_symmetric(&self, col: i32) -> bool {
+        col * self.start.den >= self.depth * self.start.num
+            && col * self.end.den <= self.depth * self.end.num
+    }
+
+    fn next(&self) -> Row {
+        Row {
+            depth: self.depth + 1,
+            ..*self
+        }
+    }
+}
+
+fn floor_div(a: i32, b: i32) -> i32 {
+    a.div_euclid(b)
+}
+
+/// The slope to the left edge of the tile at (depth, col)
+fn slope(depth: i32, col: i32) -> Slope {
+    Slope {
+        num: 2 * col - 1,
+        den: 2 * depth,
+    }
+}
+
+/// Turns a (depth, col) position within a quadrant into a map position
+fn transform(quadrant: i32, origin: Point, depth: i32, col: i32) -> Point {
+    match quadrant {
+        0 => Point::new(origin.x + col, origin.y - depth), // north
+        1 => Point::new(origin.x + depth, origin.y + col), // east
+        2 => Point::new(origin.x + col, origin.y + depth), // south
+        _ => Point::new(origin.x - depth, origin.y + col), // west
+    }
+}
+
+fn shadowcast(origin: Point, range: i32, map: &Map) -> Vec<Point> {
+    let mut visible = vec![origin];
+    for quadrant in 0..4 {
+        let first = Row {
+            depth: 1,
+            start: Slope { num: -1, den: 1 },
+            end: Slope { num: 1, den: 1 },
+        };
+        scan(quadrant, origin, range, map, first, &mut visible);
+    }
+    visible.sort_by_key(|p| (p.y, p.x));
+    visible.dedup();
+    visible
+}
+
+fn scan(
+    quadrant: i32,
+    origin: Point,
+    range: i32,
+    map: &Map,
+    mut row: Row,
+    visible: &mut Vec<Point>,
+) {
+    if row.depth > range {
+        return;
+    }
+
+    // whether the previous tile in this row was a wall, if there was one
+    let mut prev_wall: Option<bool> = None;
+    let (min_col, max_col) = row.columns();
+    for col in min_col..=max_col {
+        let p = transform(quadrant, origin, row.depth, col);
+        let wall = blocks_sight(map, p);
+
+        if (wall || row.is_symmetric(col)) && in_range(origin, p, range) && map.contains_point(p) {
+            visible.push(p);
+        }
+        if prev_wall == Some(true) && !wall {
+            row.start = slope(row.depth, col);
+        }
+        if prev_wall == Some(false) && wall {
+            let mut next = row.next();
+            next.end = slope(row.depth, col);
+            scan(quadrant, origin, range, map, next, visible);
+        }
+        prev_wall = Some(wall);
+    }
+
+    if prev_wall == Some(false) {
+        scan(quadrant, origin, range, map, row.next(), visible);
+    }
+}
+
+// Permissive field of view. Bresenham lines from A to B and from B to A
+// don't always pass through the same tiles, so a tile is counted as visible if
+// either line is clear.
+
+fn permissive(origin: Point, range: i32, map: &Map) -> Vec<Point> {
+    let mut visible = Vec::new();
+    for y in (origin.y - range)..=(origin.y + range) {
+        for x in (origin.x - range)..=(origin.x + range) {
+            let p = Point::new(x, y);
+            if map.contains_point(p)
+                && in_range(origin, p, range)
+                && (line_is_clear(map, origin, p) || line_is_clear(map, p, origin))
+            {
+                visible.push(p);
+            }
+        }
+    }
+    visible
+}
+
+/// True if nothing between `from` and `to` blocks sight. The ends of the
+/// line don't count, so walls themselves can be seen.
+fn line_is_clear(map: &Map, from: Point, to: Point) -> bool {
+    let (dx, dy) = ((to.x - from.x).abs(), -(to.y - from.y).abs());
+    let (sx, sy) = ((to.x - from.x).signum(), (to.y - from.y).signum());
+    let mut err = dx + dy;
+    let mut p = from;
+
+    loop {
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            p.x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            p.y += sy;
+        }
+        if p == to {
+            return true;
+        }
+        if blocks_sight(map, p) {
+            return false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::TileType;
+    use rltk::RandomNumberGenerator;
+    use std::collections::HashSet;
+
+    const RANGE: i32 = 8;
+
+    fn floor_tiles(map: &Map) -> Vec<Point> {
+        let mut floor = Vec::new();
+        for y in 0..map.height {
+            for x in 0..map.width {
+                if !map[(x, y)].is_opaque() {
+                    floor.push(Point::new(x, y));
+                }
+            }
+        }
+        floor
+    }
+
+    fn assert_symmetric(algorithm: FovAlgorithm) {
+        for seed in 0..3 {
+            let mut rng = RandomNumberGenerator::seeded(seed);
+            let map = Map::new_map_rooms_and_corridors(80, 50, &mut rng);
+            let floor = floor_tiles(&map);
+            let seen: Vec<HashSet<Point>> = floor
+                .iter()
+                .map(|p| {
+                    algorithm
+                        .field_of_view(*p, RANGE, &map)
+                        .into_iter()
+                        .collect()
+                })
+                .collect();
+
+            for (a, seen_from_a) in floor.iter().zip(seen.iter()) {
+                for (b, seen_from_b) in floor.iter().zip(seen.iter()) {
+                    assert_eq!(
+                        seen_from_a.contains(b),
+                        seen_from_b.contains(a),
+                        "{:?} from {:?} disagrees with {:?} from {:?}",
+                        b,
+                        a,
+                        a,
+                        b
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn shadowcasting_is_symmetric() {
+        assert_symmetric(FovAlgorithm::SymmetricShadowcasting);
+    }
+
+    #[test]
+    fn permissive_is_symmetric() {
+        assert_symmetric(FovAlgorithm::Permissive);
+    }
+
+    // A room with a single pillar in it
+    fn pillar_map() -> Map {
+        let mut map = Map::new(21, 21);
+        for y in 1..20 {
+            for x in 1..20 {
+                map[(x, y)] = TileType::Floor;
+            }
+        }
+        map[(10, 8)] = TileType::Wall;
+        map
+    }
+
+    #[test]
+    fn walls_cast_shadows_and_are_visible() {
+        let map = pillar_map();
+        for algorithm in &[
+            FovAlgorithm::SymmetricShadowcasting,
+            FovAlgorithm::Permissive,
+        ] {
+            let seen = algorithm.field_of_view(Point::new(10, 10), RANGE, &map);
+            assert!(seen.contains(&Point::new(10, 10)));
+            assert!(seen.contains(&Point::new(10, 8)), "{:?}", algorithm);
+            assert!(!seen.contains(&Point::new(10, 5)), "{:?}", algorithm);
+            assert!(seen.contains(&Point::new(10, 12)), "{:?}", algorithm);
+            assert!(seen.contains(&Point::new(10, 17)), "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn permissive_sees_at_least_as_much_as_shadowcasting() {
+        let map = pillar_map();
+        let origin = Point::new(9, 12);
+        let shadow = FovAlgorithm::SymmetricShadowcasting.field_of_view(origin, RANGE, &map);
+        let permissive: HashSet<Point> = FovAlgorithm::Permissive
+            .field_of_view(origin, RANGE, &map)
+            .into_iter()
+            .collect();
+        for p in shadow.iter().filter(|p| !map[**p].is_opaque()) {
+            assert!(permissive.contains(p), "{:?}", p);
+        }
+    }
+}
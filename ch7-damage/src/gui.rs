@@ -0,0 +1,359 @@
+// Everything drawn on the screen that isn't the map
+
+use crate::camera::Camera;
+use crate::components::{
+    CombatStats, Experience, HungerClock, HungerState, KnownSpells, LastAttacker, Mana, Name,
+    Player, Purse,
+};
+use crate::items::carried_items;
+use crate::keymap::{Action, Keymap};
+use crate::player::usable_items;
+use crate::progression::LevelUpChoice;
+use crate::systems::awareness_system::VisibleMonsters;
+use crate::systems::damage_system::killer_name;
+use crate::trade::price;
+use rltk::{Console, Point, Rltk, RGB};
+use specs::prelude::*;
+
+/// How many columns on the right of the screen the sidebar takes up
+pub const SIDEBAR_WIDTH: i32 = 20;
+
+/// Draws the sidebar, with the player's health and the monsters they can see,
+/// starting at column `x`
+pub fn draw_sidebar(ecs: &World, ctx: &mut Rltk, x: i32, height: i32) {
+    let white = RGB::named(rltk::WHITE);
+    let black = RGB::named(rltk::BLACK);
+    ctx.draw_box(x, 0, SIDEBAR_WIDTH - 1, height - 1, white, black);
+
+    let players = ecs.read_storage::<Player>();
+    let combat_stats = ecs.read_storage::<CombatStats>();
+    let names = ecs.read_storage::<Name>();
+    let hunger_clocks = ecs.read_storage::<HungerClock>();
+    let experience = ecs.read_storage::<Experience>();
+    let mana = ecs.read_storage::<Mana>();
+    let purses = ecs.read_storage::<Purse>();
+
+    for (_player, stats, hunger, experience, mana, purse) in (
+        &players,
+        &combat_stats,
+        hunger_clocks.maybe(),
+        experience.maybe(),
+        mana.maybe(),
+        purses.maybe(),
+    )
+        .join()
+    {
+        let health = format!("HP: {}/{}", stats.hp, stats.max_hp);
+        ctx.print_color(x + 1, 1, RGB::named(rltk::YELLOW), black, &health);
+        ctx.draw_bar_horizontal(
+            x + 1,
+            2,
+            SIDEBAR_WIDTH - 3,
+            stats.hp,
+            stats.max_hp,
+            RGB::named(rltk::RED),
+            black,
+        );
+
+        if let Some(mana) = mana {
+            let text = format!("MP: {}/{}", mana.current, mana.max);
+            ctx.print_color(x + 1, 3, RGB::named(rltk::CYAN), black, &text);
+            ctx.draw_bar_horizontal(
+                x + 1,
+                4,
+                SIDEBAR_WIDTH - 3,
+                mana.current,
+                mana.max,
+                RGB::named(rltk::BLUE),
+                black,
+            );
+        }
+
+        if let Some(experience) = experience {
+            let level = format!(
+                "Lvl {}  XP {}/{}",
+                experience.level,
+                experience.xp,
+                experience.next_level_xp()
+            );
+            ctx.print_color(x + 1, 5, white, black, &level);
+        }
+
+        if let Some(purse) = purse {
+            let gold = format!("Gold: {}", purse.gold);
+            ctx.print_color(x + 1, 7, RGB::named(rltk::GOLD), black, &gold);
+        }
+
+        // being normally hungry isn't worth mentioning
+        let hunger = match hunger.map(|clock| clock.state) {
+            Some(HungerState::WellFed) => Some(("Well Fed", RGB::named(rltk::GREEN))),
+            Some(HungerState::Hungry) => Some(("Hungry", RGB::named(rltk::ORANGE))),
+            Some(HungerState::Starving) => Some(("Starving", RGB::named(rltk::RED))),
+            _ => None,
+        };
+        if let Some((label, colour)) = hunger {
+            ctx.print_color(x + 1, 6, colour, black, label);
+        }
+    }
+
+    ctx.print_color(x + 1, 9, white, black, "In view:");
+    let visible = ecs.fetch::<VisibleMonsters>();
+    let mut y = 10;
+    for seen in visible.seen.iter() {
+        if y >= height - 1 {
+            break;
+        }
+
+        // the monster may have died since the list was made
+        let (name, stats) = match (names.get(seen.entity), combat_stats.get(seen.entity)) {
+            (Some(name), Some(stats)) => (name, stats),
+            _ => continue,
+        };
+
+        // a red ! means the monster can see the player
+        if seen.sees_player {
+            ctx.print_color(x + 1, y, RGB::named(rltk::RED), black, "!");
+        }
+
+        // monsters that just came into view are highlighted
+        let colour = if visible.newly_seen.contains(&seen.entity) {
+            RGB::named(rltk::YELLOW)
+        } else {
+            white
+        };
+        let mut label = name.name.clone();
+        label.truncate((SIDEBAR_WIDTH - 10) as usize);
+        ctx.print_color(x + 2, y, colour, black, &label);
+        ctx.print_color(
+            x + SIDEBAR_WIDTH - 7,
+            y,
+            white,
+            black,
+            &format!("{:>2}/{:<2}", stats.hp, stats.max_hp),
+        );
+        y += 1;
+    }
+}
+
+/// Draws a box listing every action and the keys bound to it
+pub fn draw_help(ecs: &World, ctx: &mut Rltk) {
+    let white = RGB::named(rltk::WHITE);
+    let black = RGB::named(rltk::BLACK);
+    let yellow = RGB::named(rltk::YELLOW);
+    let keymap = ecs.fetch::<Keymap>();
+
+    let (x, y) = (10, 8);
+    let height = Action::ALL.len() as i32 + 6;
+    ctx.draw_box(x, y, 50, height, white, black);
+    ctx.print_color(x + 2, y + 1, yellow, black, "Keys");
+
+    for (i, action) in Action::ALL.iter().enumerate() {
+        let keys: Vec<String> = keymap
+            .keys_for(*action)
+            .iter()
+            .map(|key| format!("{:?}", key))
+            .collect();
+        let keys = if keys.is_empty() {
+            "(not bound)".to_string()
+        } else {
+            keys.join(", ")
+        };
+
+        let row = y + 3 + i as i32;
+        ctx.print_color(x + 2, row, white, black, action.description());
+        ctx.print_color(x + 26, row, yellow, black, &keys);
+    }
+
+    ctx.print_color(x + 2, y + height - 1, white, black, "Shift+direction runs.");
+    ctx.print_color(
+        x + 26,
+        y + height - 1,
+        white,
+        black,
+        "Press any key to close.",
+    );
+}
+
+/// Draws the choices the player has when they gain a level
+pub fn draw_level_up(ctx: &mut Rltk) {
+    let white = RGB::named(rltk::WHITE);
+    let black = RGB::named(rltk::BLACK);
+    let yellow = RGB::named(rltk::YELLOW);
+
+    let (x, y) = (15, 15);
+    let height = LevelUpChoice::ALL.len() as i32 + 4;
+    ctx.draw_box(x, y, 40, height, white, black);
+    ctx.print_color(x + 2, y + 1, yellow, black, "You feel more experienced!");
+
+    for (i, choice) in LevelUpChoice::ALL.iter().enumerate() {
+        let row = y + 3 + i as i32;
+        ctx.print_color(x + 2, row, yellow, black, &format!("{}.", i + 1));
+        ctx.print_color(x + 5, row, white, black, choice.description());
+    }
+}
+
+/// Draws the screen shown when the player dies
+pub fn draw_game_over(ecs: &World, ctx: &mut Rltk) {
+    let white = RGB::named(rltk::WHITE);
+    let black = RGB::named(rltk::BLACK);
+    let red = RGB::named(rltk::RED);
+
+    let players = ecs.read_storage::<Player>();
+    let experience = ecs.read_storage::<Experience>();
+    let names = ecs.read_storage::<Name>();
+    let last_attackers = ecs.read_storage::<LastAttacker>();
+    let entities = ecs.entities();
+
+    let (x, y) = (15, 15);
+    ctx.draw_box(x, y, 40, 7, white, black);
+    ctx.print_color(x + 2, y + 1, red, black, "You are dead.");
+
+    for (player, _player, experience) in (&entities, &players, experience.maybe()).join() {
+        let cause = match killer_name(&last_attackers, &names, player) {
+            Some(killer) => format!("Killed by {}", killer),
+            None => "You died".to_string(),
+        };
+        let level = experience.map_or(1, |experience| experience.level);
+        ctx.print_color(
+            x + 2,
+            y + 3,
+            white,
+            black,
+            &format!("{} at level {}.", cause, level),
+        );
+    }
+
+    ctx.print_color(x + 2, y + 5, white, black, "Press any key to quit.");
+}
+
+/// Draws the spells the player knows, numbered, with what they cost
+pub fn draw_spell_menu(ecs: &World, ctx: &mut Rltk) {
+    let white = RGB::named(rltk::WHITE);
+    let black = RGB::named(rltk::BLACK);
+    let yellow = RGB::named(rltk::YELLOW);
+    let grey = RGB::named(rltk::GREY);
+
+    let players = ecs.read_storage::<Player>();
+    let known_spells = ecs.read_storage::<KnownSpells>();
+    let mana = ecs.read_storage::<Mana>();
+
+    for (_player, known, mana) in (&players, &known_spells, mana.maybe()).join() {
+        let current = mana.map_or(0, |mana| mana.current);
+        let (x, y) = (15, 12);
+        let height = known.spells.len() as i32 + 5;
+        ctx.draw_box(x, y, 40, height, white, black);
+        ctx.print_color(x + 2, y + 1, yellow, black, "Cast which spell?");
+
+        for (i, spell) in known.spells.iter().enumerate() {
+            let row = y + 3 + i as i32;
+            // spells the player can't afford are greyed out
+            let colour = if spell.mana_cost() <= current {
+                white
+            } else {
+                grey
+            };
+            ctx.print_color(x + 2, row, yellow, black, &format!("{}.", i + 1));
+            ctx.print_color(x + 5, row, colour, black, spell.name());
+            let cost = format!("{} mana", spell.mana_cost());
+            ctx.print_color(x + 30, row, colour, black, &cost);
+        }
+
+        ctx.print_color(x + 2, y + height - 1, white, black, "Escape to cancel.");
+    }
+}
+
+/// Draws the potions and scrolls the player is carrying, numbered
+pub fn draw_inventory(ecs: &World, ctx: &mut Rltk) {
+    let white = RGB::named(rltk::WHITE);
+    let black = RGB::named(rltk::BLACK);
+    let yellow = RGB::named(rltk::YELLOW);
+
+    let items = usable_items(ecs);
+    let (x, y) = (15, 12);
+    let height = items.len().clamp(1, 9) as i32 + 4;
+    ctx.draw_box(x, y, 40, height, white, black);
+    ctx.print_color(x + 2, y + 1, yellow, black, "Use which item?");
+
+    if items.is_empty() {
+        ctx.print_color(x + 2, y + 3, white, black, "You have nothing to use.");
+    }
+    // only nine items fit in a numbered menu
+    for (i, (_item, name)) in items.iter().take(9).enumerate() {
+        let row = y + 3 + i as i32;
+        ctx.print_color(x + 2, row, yellow, black, &format!("{}.", i + 1));
+        ctx.print_color(x + 5, row, white, black, name);
+    }
+
+    ctx.print_color(x + 2, y + height - 1, white, black, "Escape to cancel.");
+}
+
+/// Highlights the tiles a spell can be aimed at, the one picked with Tab,
+/// and the one under the mouse
+pub fn draw_targeting(ctx: &mut Rltk, camera: &Camera, targets: &[Point], cursor: Option<Point>) {
+    let black = RGB::named(rltk::BLACK);
+    let yellow = RGB::named(rltk::YELLOW);
+    ctx.print_color(
+        1,
+        0,
+        yellow,
+        black,
+        "Click, or Tab then Enter, to target. Escape cancels.",
+    );
+
+    for p in targets.iter() {
+        if let Some((sx, sy)) = camera.world_to_screen(p.x, p.y) {
+            ctx.set_bg(sx, sy, RGB::named(rltk::DARK_BLUE));
+        }
+    }
+
+    if let Some((sx, sy)) = cursor.and_then(|p| camera.world_to_screen(p.x, p.y)) {
+        ctx.set_bg(sx, sy, RGB::named(rltk::CYAN));
+    }
+
+    let (mx, my) = ctx.mouse_pos();
+    let (x, y) = camera.screen_to_world(mx, my);
+    if targets.contains(&Point::new(x, y)) {
+        ctx.set_bg(mx, my, RGB::named(rltk::CYAN));
+    }
+}
+
+/// Draws the trading screen: the vendor's wares when buying, or the
+/// player's backpack when selling, with prices
+pub fn draw_trade(ecs: &World, ctx: &mut Rltk, vendor: Entity, selling: bool) {
+    let white = RGB::named(rltk::WHITE);
+    let black = RGB::named(rltk::BLACK);
+    let yellow = RGB::named(rltk::YELLOW);
+
+    let players = ecs.read_storage::<Player>();
+    let entities = ecs.entities();
+    let player = match (&entities, &players).join().next() {
+        Some((player, _player)) => player,
+        None => return,
+    };
+
+    let (title, owner) = if selling {
+        ("Sell what? (Tab to buy)", player)
+    } else {
+        ("Buy what? (Tab to sell)", vendor)
+    };
+    let items = carried_items(ecs, owner);
+
+    let (x, y) = (10, 10);
+    let height = items.len() as i32 + 6;
+    ctx.draw_box(x, y, 45, height, white, black);
+    ctx.print_color(x + 2, y + 1, yellow, black, title);
+
+    if items.is_empty() {
+        ctx.print_color(x + 2, y + 3, white, black, "Nothing.");
+    }
+    // only nine items fit in a numbered menu
+    for (i, (item, name)) in items.iter().take(9).enumerate() {
+        let row = y + 3 + i as i32;
+        ctx.print_color(x + 2, row, yellow, black, &format!("{}.", i + 1));
+        ctx.print_color(x + 5, row, white, black, name);
+        let cost = format!("{} gold", price(ecs, vendor, *item, selling));
+        ctx.print_color(x + 34, row, white, black, &cost);
+    }
+
+    ctx.print_color(x + 2, y + height - 1, white, black, "Escape to leave.");
+}
@@ -0,0 +1,82 @@
+// Checks for things that should stop the player doing something that takes
+// more than one turn, like exploring or travelling
+
+use crate::components::{CombatStats, Player, Position};
+use crate::systems::awareness_system::VisibleMonsters;
+use crate::systems::memory_system::EntityMemory;
+use rltk::{DistanceAlg, Point};
+use specs::prelude::*;
+
+/// The parts of the game that stop a multi-turn action when they change
+pub struct Snapshot {
+    hp: i32,
+    // things that aren't monsters that the player has seen
+    items_seen: usize,
+    // how far away each monster in view is
+    monster_distances: Vec<(Entity, f32)>,
+}
+
+impl Snapshot {
+    pub fn take(ecs: &World) -> Snapshot {
+        let players = ecs.read_storage::<Player>();
+        let combat_stats = ecs.read_storage::<CombatStats>();
+        let hp = (&players, &combat_stats)
+            .join()
+            .map(|(_p, stats)| stats.hp)
+            .next()
+            .unwrap_or(0);
+
+        let memory = ecs.fetch::<EntityMemory>();
+        let items_seen = memory
+            .ghosts
+            .values()
+            .filter(|ghost| ghost.turns_left.is_none())
+            .count();
+
+        let player_position = *ecs.fetch::<Point>();
+        let positions = ecs.read_storage::<Position>();
+        let monster_distances = ecs
+            .fetch::<VisibleMonsters>()
+            .seen
+            .iter()
+            .filter_map(|seen| {
+                let pos = positions.get(seen.entity)?;
+                let distance =
+                    DistanceAlg::Pythagoras.distance2d(player_position, Point::new(pos.x, pos.y));
+                Some((seen.entity, distance))
+            })
+            .collect();
+
+        Snapshot {
+            hp,
+            items_seen,
+            monster_distances,
+        }
+    }
+
+    /// Returns why the player should stop, if anything interesting happened
+    /// since the snapshot was taken
+    pub fn interruption(&self, ecs: &World) -> Option<&'static str> {
+        let now = Snapshot::take(ecs);
+        let visible = ecs.fetch::<VisibleMonsters>();
+        // monsters that were already in view matter too, if they're coming
+        let closing_in = now.monster_distances.iter().any(|(entity, distance)| {
+            self.monster_distances
+                .iter()
+                .any(|(before, was)| before == entity && distance < was)
+        });
+        if !visible.newly_seen.is_empty() {
+            Some("a monster comes into view")
+        } else if visible.seen.iter().any(|seen| seen.sees_player) {
+            Some("a monster can see you")
+        } else if closing_in {
+            Some("a monster is getting closer")
+        } else if now.items_seen > self.items_seen {
+            Some("you spot something")
+        } else if now.hp < self.hp {
+            Some("you've been hurt")
+        } else {
+            None
+        }
+    }
+}
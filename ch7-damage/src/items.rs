@@ -0,0 +1,377 @@
+// Carried items, using them, and identifying them. Potions and scrolls start
+// out unidentified: each game gives every kind a random description, and
+// its real name is only shown once the player knows what it is.
+
+use crate::components::{
+    CombatStats, Consumable, DamageType, InBackpack, Mana, Name, Player, Position, SuffersDamage,
+    Viewshed,
+};
+use crate::map::Map;
+use rltk::{console, Point, RandomNumberGenerator};
+use specs::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+/// The kinds of potions and scrolls
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+pub enum ConsumableKind {
+    HealingPotion,
+    ManaPotion,
+    PoisonPotion,
+    IdentifyScroll,
+    TeleportScroll,
+    MappingScroll,
+}
+
+impl ConsumableKind {
+    pub const ALL: [ConsumableKind; 6] = [
+        ConsumableKind::HealingPotion,
+        ConsumableKind::ManaPotion,
+        ConsumableKind::PoisonPotion,
+        ConsumableKind::IdentifyScroll,
+        ConsumableKind::TeleportScroll,
+        ConsumableKind::MappingScroll,
+    ];
+
+    /// What the item is called once it has been identified
+    pub fn name(self) -> &'static str {
+        match self {
+            ConsumableKind::HealingPotion => "Potion of Healing",
+            ConsumableKind::ManaPotion => "Potion of Mana",
+            ConsumableKind::PoisonPotion => "Potion of Poison",
+            ConsumableKind::IdentifyScroll => "Scroll of Identify",
+            ConsumableKind::TeleportScroll => "Scroll of Teleport",
+            ConsumableKind::MappingScroll => "Scroll of Magic Mapping",
+        }
+    }
+
+    pub fn is_potion(self) -> bool {
+        matches!(
+            self,
+            ConsumableKind::HealingPotion
+                | ConsumableKind::ManaPotion
+                | ConsumableKind::PoisonPotion
+        )
+    }
+
+    /// What the item is worth to a vendor
+    pub fn value(self) -> i32 {
+        match self {
+            ConsumableKind::HealingPotion => 30,
+            ConsumableKind::ManaPotion => 25,
+            ConsumableKind::PoisonPotion => 5,
+            ConsumableKind::IdentifyScroll => 20,
+            ConsumableKind::TeleportScroll => 25,
+            ConsumableKind::MappingScroll => 40,
+        }
+    }
+}
+
+const POTION_LOOKS: [&str; 6] = ["bubbling", "murky", "fizzy", "glowing", "smoky", "thick"];
+const POTION_COLOURS: [&str; 6] = ["red", "blue", "green", "yellow", "violet", "black"];
+const SCROLL_SYLLABLES: [&str; 10] = [
+    "xu", "zor", "bel", "ka", "mi", "thar", "nok", "ve", "lo", "qua",
+];
+
+/// Resource holding what every kind of consumable looks like this game, and
+/// which kinds the player has identified
+pub struct KnownItems {
+    descriptions: HashMap<ConsumableKind, String>,
+    known: HashSet<ConsumableKind>,
+}
+
+impl KnownItems {
+    /// Makes up a different description for every kind
+    pub fn new(rng: &mut RandomNumberGenerator) -> KnownItems {
+        let mut descriptions: HashMap<ConsumableKind, String> = HashMap::new();
+        for kind in ConsumableKind::ALL.iter() {
+            let description = loop {
+                let description = if kind.is_potion() {
+                    format!(
+                        "{} {} potion",
+                        POTION_LOOKS[rng.range(0, POTION_LOOKS.len())],
+                        POTION_COLOURS[rng.range(0, POTION_COLOURS.len())]
+                    )
+                } else {
+                    let label: String = (0..rng.range(2, 4))
+                        .map(|_| SCROLL_SYLLABLES[rng.range(0, SCROLL_SYLLABLES.len())])
+                        .collect();
+                    format!("scroll labelled {}", label.to_uppercase())
+                };
+                if !descriptions.values().any(|d| *d == description) {
+                    break description;
+                }
+            };
+            descriptions.insert(*kind, description);
+        }
+
+        KnownItems {
+            descriptions,
+            known: HashSet::new(),
+        }
+    }
+
+    pub fn is_known(&self, kind: ConsumableKind) -> bool {
+        self.known.contains(&kind)
+    }
+
+    pub fn identify(&mut self, kind: ConsumableKind) {
+        self.known.insert(kind);
+    }
+
+    /// The real name of the kind if it's known, otherwise its description
+    pub fn name(&self, kind: ConsumableKind) -> String {
+        if self.is_known(kind) {
+            kind.name().to_string()
+        } else {
+            self.descriptions[&kind].clone()
+        }
+    }
+}
+
+/// What the player calls an item. Unidentified potions and scrolls go by
+/// their description rather than their real name.
+pub fn display_name(ecs: &World, item: Entity) -> String {
+    if let Some(consumable) = ecs.read_storage::<Consumable>().get(item) {
+        return ecs.fetch::<KnownItems>().name(consumable.kind);
+    }
+    ecs.read_storage::<Name>()
+        .get(item)
+        .map_or("something".to_string(), |name| name.name.clone())
+}
+
+/// Everything `owner` is carrying, with display names. Sorted by entity id,
+/// so menus list things in the same order each time they're drawn.
+pub fn carried_items(ecs: &World, owner: Entity) -> Vec<(Entity, String)> {
+    let entities = ecs.entities();
+    let backpacks = ecs.read_storage::<InBackpack>();
+    let mut items: Vec<Entity> = (&entities, &backpacks)
+        .join()
+        .filter(|(_item, backpack)| backpack.owner == owner)
+        .map(|(item, _backpack)| item)
+        .collect();
+    items.sort_by_key(|item| item.id());
+    items
+        .into_iter()
+        .map(|item| (item, display_name(ecs, item)))
+        .collect()
+}
+
+/// Drinks or reads a carried potion or scroll, which identifies it. Returns
+/// false if the item isn't something that can be used.
+pub fn use_item(ecs: &mut World, user: Entity, item: Entity) -> bool {
+    let kind = match ecs.read_storage::<Consumable>().get(item) {
+        None => return false,
+        Some(consumable) => consumable.kind,
+    };
+    let verb = if kind.is_potion() { "drink" } else { "read" };
+    console::log(format!("You {} the {}.", verb, display_name(ecs, item)));
+    ecs.delete_entity(item).expect("Unable to delete item.");
+
+    match kind {
+        ConsumableKind::HealingPotion => {
+            let amount = ecs
+                .write_resource::<RandomNumberGenerator>()
+                .roll_dice(2, 6)
+                + 4;
+            if let Some(stats) = ecs.write_storage::<CombatStats>().get_mut(user) {
+                stats.hp = i32::min(stats.max_hp, stats.hp + amount);
+            }
+            console::log("You feel better.");
+        }
+        ConsumableKind::ManaPotion => {
+            if let Some(mana) = ecs.write_storage::<Mana>().get_mut(user) {
+                mana.current = mana.max;
+            }
+            console::log("Your mind clears.");
+        }
+        ConsumableKind::PoisonPotion => {
+            let amount = ecs
+                .write_resource::<RandomNumberGenerator>()
+                .roll_dice(1, 6);
+            let mut suffer_damage = ecs.write_storage::<SuffersDamage>();
+            SuffersDamage::new_damage(&mut suffer_damage, user, amount, DamageType::Poison, None);
+            console::log("That tasted awful.");
+        }
+        ConsumableKind::IdentifyScroll => identify_carried(ecs, user),
+        ConsumableKind::TeleportScroll => teleport(ecs, user),
+        ConsumableKind::MappingScroll => {
+            let mut map = ecs.fetch_mut::<Map>();
+            for revealed in map.revealed_tiles.iter_mut() {
+                *revealed = true;
+            }
+            console::log("The layout of the level fills your mind.");
+        }
+    }
+
+    let mut known = ecs.fetch_mut::<KnownItems>();
+    if !known.is_known(kind) {
+        known.identify(kind);
+        console::log(format!("It was a {}.", kind.name()));
+    }
+    true
+}
+
+/// Identifies the first unknown kind of item `user` is carrying
+fn identify_carried(ecs: &mut World, user: Entity) {
+    let kind = {
+        let known = ecs.fetch::<KnownItems>();
+        let consumables = ecs.read_storage::<Consumable>();
+        carried_items(ecs, user)
+            .iter()
+            .filter_map(|(item, _name)| consumables.get(*item))
+            .map(|consumable| consumable.kind)
+            .find(|kind| !known.is_known(*kind))
+    };
+    match kind {
+        Some(kind) => {
+            let description = ecs.fetch::<KnownItems>().name(kind);
+            ecs.fetch_mut::<KnownItems>().identify(kind);
+            console::log(format!("The {} is a {}.", description, kind.name()));
+        }
+        None => console::log("You learn nothing new."),
+    }
+}
+
+/// Sends `user` to a random spot on the map
+fn teleport(ecs: &mut World, user: Entity) {
+    let destination = {
+        let map = ecs.fetch::<Map>();
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        map.random_floor_tile(&mut rng)
+    };
+    let destination = match destination {
+        Some(destination) => destination,
+        None => return,
+    };
+
+    if let Some(pos) = ecs.write_storage::<Position>().get_mut(user) {
+        // so nothing walks onto either tile wrongly before the map is
+        // indexed again
+        let mut map = ecs.fetch_mut::<Map>();
+        let (here, there) = (map.idx(pos.x, pos.y), map.point_idx(destination));
+        map.blocked[here] = false;
+        map.blocked[there] = true;
+        pos.x = destination.x;
+        pos.y = destination.y;
+    }
+    if let Some(viewshed) = ecs.write_storage::<Viewshed>().get_mut(user) {
+        viewshed.dirty = true;
+    }
+    if ecs.read_storage::<Player>().get(user).is_some() {
+        *ecs.write_resource::<Point>() = destination;
+    }
+    console::log("You are somewhere else.");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::{Experience, LastAttacker, Resistances, XpReward};
+    use crate::map::TileType;
+    use crate::systems::damage_system::DamageSystem;
+
+    #[test]
+    fn every_kind_looks_different() {
+        for seed in 0..20 {
+            let known = KnownItems::new(&mut RandomNumberGenerator::seeded(seed));
+            let names: HashSet<String> =
+                ConsumableKind::ALL.iter().map(|k| known.name(*k)).collect();
+            assert_eq!(names.len(), ConsumableKind::ALL.len());
+        }
+    }
+
+    #[test]
+    fn identifying_reveals_the_real_name() {
+        let mut known = KnownItems::new(&mut RandomNumberGenerator::seeded(1));
+        let kind = ConsumableKind::HealingPotion;
+        assert!(known.name(kind).ends_with("potion"));
+        known.identify(kind);
+        assert_eq!(known.name(kind), "Potion of Healing");
+        assert!(known
+            .name(ConsumableKind::TeleportScroll)
+            .starts_with("scroll labelled"));
+    }
+
+    #[test]
+    fn descriptions_are_the_same_for_the_same_seed() {
+        let a = KnownItems::new(&mut RandomNumberGenerator::seeded(7));
+        let b = KnownItems::new(&mut RandomNumberGenerator::seeded(7));
+        for kind in ConsumableKind::ALL.iter() {
+            assert_eq!(a.name(*kind), b.name(*kind));
+        }
+    }
+
+    #[test]
+    fn teleporting_moves_the_blocked_tile() {
+        let mut ecs = World::new();
+        ecs.register::<Position>();
+        ecs.register::<Viewshed>();
+        ecs.register::<Player>();
+        ecs.insert(RandomNumberGenerator::seeded(3));
+        ecs.insert(Point::new(1, 1));
+        let mut map = Map::new(10, 10);
+        map[(1, 1)] = TileType::Floor;
+        map[(6, 6)] = TileType::Floor;
+        map.populate_blocked();
+        let start = map.idx(1, 1);
+        map.blocked[start] = true;
+        ecs.insert(map);
+
+        let player = ecs
+            .create_entity()
+            .with(Position::new(1, 1))
+            .with(Player {})
+            .build();
+        teleport(&mut ecs, player);
+
+        let map = ecs.fetch::<Map>();
+        assert!(!map.blocked[map.idx(1, 1)]);
+        assert!(map.blocked[map.idx(6, 6)]);
+        assert_eq!(*ecs.fetch::<Point>(), Point::new(6, 6));
+    }
+
+    #[test]
+    fn poisoning_yourself_isnt_blamed_on_your_last_attacker() {
+        let mut ecs = World::new();
+        ecs.register::<Consumable>();
+        ecs.register::<Name>();
+        ecs.register::<Position>();
+        ecs.register::<CombatStats>();
+        ecs.register::<SuffersDamage>();
+        ecs.register::<Resistances>();
+        ecs.register::<LastAttacker>();
+        ecs.register::<Experience>();
+        ecs.register::<XpReward>();
+        ecs.register::<Player>();
+        let mut rng = RandomNumberGenerator::seeded(1);
+        ecs.insert(KnownItems::new(&mut rng));
+        ecs.insert(rng);
+        ecs.insert(Map::new(10, 10));
+
+        let goblin = ecs
+            .create_entity()
+            .with(Experience { level: 1, xp: 0 })
+            .build();
+        let player = ecs
+            .create_entity()
+            .with(CombatStats::new(1, 1, 0, 0))
+            .with(LastAttacker { entity: goblin })
+            .with(XpReward { xp: 10 })
+            .build();
+        let potion = ecs
+            .create_entity()
+            .with(Consumable {
+                kind: ConsumableKind::PoisonPotion,
+            })
+            .build();
+
+        assert!(use_item(&mut ecs, player, potion));
+        DamageSystem {}.run_now(&ecs);
+        assert!(ecs.read_storage::<CombatStats>().get(player).unwrap().hp < 1);
+        assert!(ecs.read_storage::<LastAttacker>().get(player).is_none());
+        assert_eq!(ecs.read_storage::<Experience>().get(goblin).unwrap().xp, 0);
+        assert!(ecs
+            .fetch::<KnownItems>()
+            .is_known(ConsumableKind::PoisonPotion));
+    }
+}
@@ -0,0 +1,436 @@
+// Key bindings. The game only deals in Actions; which key does what comes
+// from a Keymap, loaded from a config file.
+
+use rltk::VirtualKeyCode;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+
+/// Where the key bindings are read from, relative to where the game is run
+pub const CONFIG_FILE: &str = "keys.cfg";
+
+/// Everything the player can ask to do
+#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug)]
+pub enum Action {
+    MoveNorth,
+    MoveSouth,
+    MoveEast,
+    MoveWest,
+    MoveNorthEast,
+    MoveNorthWest,
+    MoveSouthEast,
+    MoveSouthWest,
+    Wait,
+    Rest,
+    Search,
+    PickUp,
+    Eat,
+    Cast,
+    UseItem,
+    AutoExplore,
+    Help,
+}
+
+impl Action {
+    /// Every action, in the order the help screen lists them
+    pub const ALL: [Action; 17] = [
+        Action::MoveNorth,
+        Action::MoveSouth,
+        Action::MoveEast,
+        Action::MoveWest,
+        Action::MoveNorthEast,
+        Action::MoveNorthWest,
+        Action::MoveSouthEast,
+        Action::MoveSouthWest,
+        Action::Wait,
+        Action::Rest,
+        Action::Search,
+        Action::PickUp,
+        Action::Eat,
+        Action::Cast,
+        Action::UseItem,
+        Action::AutoExplore,
+        Action::Help,
+    ];
+
+    /// The name used for the action in the config file
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::MoveNorth => "north",
+            Action::MoveSouth => "south",
+            Action::MoveEast => "east",
+            Action::MoveWest => "west",
+            Action::MoveNorthEast => "northeast",
+            Action::MoveNorthWest => "northwest",
+            Action::MoveSouthEast => "southeast",
+            Action::MoveSouthWest => "southwest",
+            Action::Wait => "wait",
+            Action::Rest => "rest",
+            Action::Search => "search",
+            Action::PickUp => "pickup",
+            Action::Eat => "eat",
+            Action::Cast => "cast",
+            Action::UseItem => "use",
+            Action::AutoExplore => "explore",
+            Action::Help => "help",
+        }
+    }
+
+    /// What the action does, for the help screen
+    pub fn description(self) -> &'static str {
+        match self {
+            Action::MoveNorth => "Move up",
+            Action::MoveSouth => "Move down",
+            Action::MoveEast => "Move right",
+            Action::MoveWest => "Move left",
+            Action::MoveNorthEast => "Move up and right",
+            Action::MoveNorthWest => "Move up and left",
+            Action::MoveSouthEast => "Move down and right",
+            Action::MoveSouthWest => "Move down and left",
+            Action::Wait => "Wait a turn",
+            Action::Rest => "Rest until healed",
+            Action::Search => "Search nearby",
+            Action::PickUp => "Pick up items",
+            Action::Eat => "Eat something",
+            Action::Cast => "Cast a spell",
+            Action::UseItem => "Use an item",
+            Action::AutoExplore => "Explore automatically",
+            Action::Help => "Show this help",
+        }
+    }
+
+    /// The direction a movement action moves in
+    pub fn direction(self) -> Option<(i32, i32)> {
+        match self {
+            Action::MoveNorth => Some((0, -1)),
+            Action::MoveSouth => Some((0, 1)),
+            Action::MoveEast => Some((1, 0)),
+            Action::MoveWest => Some((-1, 0)),
+            Action::MoveNorthEast => Some((1, -1)),
+            Action::MoveNorthWest => Some((-1, -1)),
+            Action::MoveSouthEast => Some((1, 1)),
+            Action::MoveSouthWest => Some((-1, 1)),
+            _ => None,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.iter().copied().find(|a| a.name() == name)
+    }
+}
+
+/// The built-in sets of movement keys
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Preset {
+    Wasd,
+    Numpad,
+    ViKeys,
+    Arrows,
+}
+
+impl Preset {
+    fn from_name(name: &str) -> Option<Preset> {
+        match name {
+            "wasd" => Some(Preset::Wasd),
+            "numpad" => Some(Preset::Numpad),
+            "vi" => Some(Preset::ViKeys),
+            "arrows" => Some(Preset::Arrows),
+            _ => None,
+        }
+    }
+
+    /// The preset's movement keys, and the way each one moves. Arrow keys
+    /// have no diagonals.
+    fn movement_keys(self) -> Vec<(VirtualKeyCode, Action)> {
+        use Action::*;
+        use VirtualKeyCode::*;
+        match self {
+            Preset::Wasd => vec![
+                (W, MoveNorth),
+                (S, MoveSouth),
+                (D, MoveEast),
+                (A, MoveWest),
+                (E, MoveNorthEast),
+                (Q, MoveNorthWest),
+                (X, MoveSouthEast),
+                (Z, MoveSouthWest),
+            ],
+            Preset::Numpad => vec![
+                (Numpad8, MoveNorth),
+                (Numpad2, MoveSouth),
+                (Numpad6, MoveEast),
+                (Numpad4, MoveWest),
+                (Numpad9, MoveNorthEast),
+                (Numpad7, MoveNorthWest),
+                (Numpad3, MoveSouthEast),
+                (Numpad1, MoveSouthWest),
+            ],
+            Preset::ViKeys => vec![
+                (K, MoveNorth),
+                (J, MoveSouth),
+                (L, MoveEast),
+                (H, MoveWest),
+                (U, MoveNorthEast),
+                (Y, MoveNorthWest),
+                (N, MoveSouthEast),
+                (B, MoveSouthWest),
+            ],
+            Preset::Arrows => vec![
+                (Up, MoveNorth),
+                (Down, MoveSouth),
+                (Right, MoveEast),
+                (Left, MoveWest),
+            ],
+        }
+    }
+
+    /// The preset's bindings, including the keys every preset shares
+    fn bindings(self) -> Vec<(VirtualKeyCode, Action)> {
+        let mut bindings = self.movement_keys();
+        bindings.push((VirtualKeyCode::Period, Action::Wait));
+        bindings.push((VirtualKeyCode::R, Action::Rest));
+        bindings.push((VirtualKeyCode::F, Action::Search));
+        bindings.push((VirtualKeyCode::Comma, Action::PickUp));
+        bindings.push((VirtualKeyCode::G, Action::Eat));
+        bindings.push((VirtualKeyCode::M, Action::Cast));
+        bindings.push((VirtualKeyCode::I, Action::UseItem));
+        bindings.push((VirtualKeyCode::O, Action::AutoExplore));
+        bindings.push((VirtualKeyCode::F1, Action::Help));
+        bindings
+    }
+}
+
+/// Ways loading a keymap can fail
+#[derive(Debug)]
+pub enum KeymapError {
+    Io(std::io::Error),
+    Parse {
+        line: usize,
+        message: String,
+    },
+    Conflict {
+        key: VirtualKeyCode,
+        first: Action,
+        second: Action,
+    },
+}
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeymapError::Io(e) => write!(f, "couldn't read {}: {}", CONFIG_FILE, e),
+            KeymapError::Parse { line, message } => {
+                write!(f, "{} line {}: {}", CONFIG_FILE, line, message)
+            }
+            KeymapError::Conflict { key, first, second } => write!(
+                f,
+                "{:?} is bound to both {} and {}",
+                key,
+                first.name(),
+                second.name()
+            ),
+        }
+    }
+}
+
+/// Which key does which action
+pub struct Keymap {
+    bindings: BTreeMap<VirtualKeyCode, Action>,
+}
+
+impl Keymap {
+    /// A keymap holding just a preset's bindings
+    pub fn from_preset(preset: Preset) -> Keymap {
+        let mut keymap = Keymap {
+            bindings: BTreeMap::new(),
+        };
+        for (key, action) in preset.bindings() {
+            keymap.bind(key, action).expect("Presets don't conflict.");
+        }
+        keymap
+    }
+
+    /// Loads the keymap from the config file. If there isn't one, the WASD
+    /// preset is used.
+    pub fn load() -> Result<Keymap, KeymapError> {
+        match fs::read_to_string(CONFIG_FILE) {
+            Ok(text) => Keymap::parse(&text),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Ok(Keymap::from_preset(Preset::Wasd))
+            }
+            Err(e) => Err(KeymapError::Io(e)),
+        }
+    }
+
+    /// Parses a config file. Each line is either `preset = <name>`, which
+    /// starts from one of the presets, or `<key> = <action>`. Anything after a
+    /// `#` is a comment.
+    pub fn parse(text: &str) -> Result<Keymap, KeymapError> {
+        let mut keymap = Keymap {
+            bindings: BTreeMap::new(),
+        };
+
+        for (i, line) in text.lines().enumerate() {
+            let line_number = i + 1;
+            let parse_error = |message: String| KeymapError::Parse {
+                line: line_number,
+                message,
+            };
+
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, '=').map(|part| part.trim());
+            let (left, right) = match (parts.next(), parts.next()) {
+                (Some(left), Some(right)) => (left, right),
+                _ => return Err(parse_error("expected `<key> = <action>`".to_string())),
+            };
+
+            if left == "preset" {
+                let preset = Preset::from_name(right)
+                    .ok_or_else(|| parse_error(format!("unknown preset `{}`", right)))?;
+                for (key, action) in preset.bindings() {
+                    keymap.bind(key, action)?;
+                }
+            } else {
+                let key = key_from_name(left)
+                    .ok_or_else(|| parse_error(format!("unknown key `{}`", left)))?;
+                let action = Action::from_name(right)
+                    .ok_or_else(|| parse_error(format!("unknown action `{}`", right)))?;
+                keymap.bind(key, action)?;
+            }
+        }
+
+        Ok(keymap)
+    }
+
+    /// Binds a key to an action. A key can only do one thing.
+    fn bind(&mut self, key: VirtualKeyCode, action: Action) -> Result<(), KeymapError> {
+        match self.bindings.get(&key) {
+            Some(existing) if *existing != action => Err(KeymapError::Conflict {
+                key,
+                first: *existing,
+                second: action,
+            }),
+            _ => {
+                self.bindings.insert(key, action);
+                Ok(())
+            }
+        }
+    }
+
+    /// The action a key is bound to, if any
+    pub fn action(&self, key: VirtualKeyCode) -> Option<Action> {
+        self.bindings.get(&key).copied()
+    }
+
+    /// The keys bound to an action
+    pub fn keys_for(&self, action: Action) -> Vec<VirtualKeyCode> {
+        self.bindings
+            .iter()
+            .filter(|(_key, a)| **a == action)
+            .map(|(key, _a)| *key)
+            .collect()
+    }
+}
+
+/// Keys that can be named in the config file, by their `VirtualKeyCode` name
+const NAMED_KEYS: [VirtualKeyCode; 70] = {
+    use VirtualKeyCode::*;
+    [
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z, Key0, Key1,
+        Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Numpad0, Numpad1, Numpad2, Numpad3,
+        Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9, Up, Down, Left, Right, Home, End,
+        PageUp, PageDown, Insert, Delete, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, Space, Period,
+        Comma, Slash,
+    ]
+};
+
+fn key_from_name(name: &str) -> Option<VirtualKeyCode> {
+    NAMED_KEYS
+        .iter()
+        .copied()
+        .find(|key| format!("{:?}", key).eq_ignore_ascii_case(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_bind_every_direction() {
+        for preset in &[Preset::Wasd, Preset::Numpad, Preset::ViKeys] {
+            let keymap = Keymap::from_preset(*preset);
+            for action in Action::ALL.iter().filter(|a| a.direction().is_some()) {
+                assert_eq!(keymap.keys_for(*action).len(), 1, "{:?}", preset);
+            }
+        }
+    }
+
+    #[test]
+    fn preset_keys_move_the_right_way() {
+        let keymap = Keymap::from_preset(Preset::ViKeys);
+        assert_eq!(keymap.action(VirtualKeyCode::H), Some(Action::MoveWest));
+        assert_eq!(
+            keymap.action(VirtualKeyCode::Y),
+            Some(Action::MoveNorthWest)
+        );
+        let keymap = Keymap::from_preset(Preset::Arrows);
+        assert_eq!(keymap.action(VirtualKeyCode::Right), Some(Action::MoveEast));
+    }
+
+    #[test]
+    fn presets_can_be_combined() {
+        let keymap = Keymap::parse("preset = vi\npreset = arrows\n").unwrap();
+        assert_eq!(keymap.action(VirtualKeyCode::K), Some(Action::MoveNorth));
+        assert_eq!(keymap.action(VirtualKeyCode::Up), Some(Action::MoveNorth));
+        assert_eq!(keymap.keys_for(Action::MoveNorth).len(), 2);
+    }
+
+    #[test]
+    fn parses_bindings_and_comments() {
+        let text = "# movement\npreset = numpad # comment\nspace = explore\n\nslash = help\n";
+        let keymap = Keymap::parse(text).unwrap();
+        assert_eq!(
+            keymap.action(VirtualKeyCode::Space),
+            Some(Action::AutoExplore)
+        );
+        assert_eq!(keymap.action(VirtualKeyCode::Slash), Some(Action::Help));
+        assert_eq!(
+            keymap.action(VirtualKeyCode::Numpad1),
+            Some(Action::MoveSouthWest)
+        );
+        assert_eq!(keymap.action(VirtualKeyCode::W), None);
+    }
+
+    #[test]
+    fn detects_conflicts() {
+        // E is already north-east in the WASD preset
+        match Keymap::parse("preset = wasd\ne = explore\n") {
+            Err(KeymapError::Conflict { key, first, second }) => {
+                assert_eq!(key, VirtualKeyCode::E);
+                assert_eq!(first, Action::MoveNorthEast);
+                assert_eq!(second, Action::AutoExplore);
+            }
+            _ => panic!("expected a conflict"),
+        }
+    }
+
+    #[test]
+    fn rebinding_a_key_to_the_same_action_is_fine() {
+        assert!(Keymap::parse("preset = wasd\nw = north\n").is_ok());
+    }
+
+    #[test]
+    fn reports_bad_lines() {
+        match Keymap::parse("preset = wasd\nw north\n") {
+            Err(KeymapError::Parse { line, .. }) => assert_eq!(line, 2),
+            _ => panic!("expected a parse error"),
+        }
+        assert!(Keymap::parse("preset = dvorak").is_err());
+        assert!(Keymap::parse("banana = north").is_err());
+        assert!(Keymap::parse("w = fly").is_err());
+    }
+}
@@ -3,26 +3,77 @@ use rltk::{Console, GameState, Point, Rltk, RGB};
 // extern crate specs;
 use specs::prelude::*;
 
+mod auto_explore;
+use auto_explore::auto_explore_turn;
+mod camera;
+use camera::Camera;
+mod combat;
 mod components;
 use components::*;
+mod factions;
+use factions::{FactionId, Reactions};
+mod fov;
+use fov::FovAlgorithm;
+mod gui;
+mod interrupt;
+mod items;
+use items::{ConsumableKind, KnownItems};
+mod keymap;
+use keymap::{Keymap, Preset};
 mod rect;
 use rect::Rect;
+mod spells;
+use spells::Spell;
 mod map;
 use map::*;
 mod player;
 use player::*;
+mod progression;
 mod systems;
+mod trade;
+mod travel;
+use systems::awareness_system::{AwarenessSystem, VisibleMonsters};
 use systems::damage_system::{delete_the_dead, DamageSystem};
+use systems::hazard_system::HazardSystem;
+use systems::hunger_system::HungerSystem;
+use systems::lighting_system::LightingSystem;
 use systems::map_indexing_system::MapIndexingSystem;
 use systems::melee_combat_system::MeleeCombatSystem;
+use systems::memory_system::{EntityMemory, MemorySystem};
 use systems::monster_ai_system::MonsterAI;
+use systems::particle_system::{cull_dead_particles, ParticleBuilder, ParticleSpawnSystem};
+use systems::perception_system::PerceptionSystem;
+use systems::regen_system::RegenSystem;
+use systems::spell_system::SpellSystem;
+use systems::trap_system::TrapSystem;
 use systems::visibility_system::VisibilitySystem;
+use travel::{run_turn, travel_turn, TravelPath};
+
+// The size of the terminal window, in characters
+const SCREEN_WIDTH: i32 = 80;
+const SCREEN_HEIGHT: i32 = 50;
+
+// The map does not need to fit on the screen; the camera follows the player
+const MAP_WIDTH: i32 = 120;
+const MAP_HEIGHT: i32 = 80;
 
 // Allows us to "pause" the game
 #[derive(PartialEq, Copy, Clone)]
 pub enum RunState {
     Paused,
     Running,
+    AutoExplore, // walking towards unexplored areas, a step per frame
+    Travel,      // walking to a tile that was clicked on
+    Run { dx: i32, dy: i32 },
+    Rest { turns: i32 }, // waiting until healed
+    Help,                // showing the key bindings
+    LevelUp,             // choosing what to improve
+    GameOver,            // the player has died
+    SpellMenu,           // choosing a spell to cast
+    Inventory,           // choosing an item to use
+    // picking where to cast a spell
+    Targeting { spell: Spell, cursor: Option<Point> },
+    Trade { vendor: Entity, selling: bool },
 }
 
 /*
@@ -35,23 +86,63 @@ pub struct State {
 }
 
 impl State {
+    /// The camera, centered on the player, for the part of the screen that
+    /// shows the map
+    fn camera(&self) -> Camera {
+        let view_width = SCREEN_WIDTH - gui::SIDEBAR_WIDTH;
+        Camera::new(*self.ecs.fetch::<Point>(), view_width, SCREEN_HEIGHT)
+    }
+
     fn run_systems(&mut self) {
+        let mut lighting = LightingSystem {};
+        lighting.run_now(&self.ecs);
+
         let mut vis = VisibilitySystem {};
         vis.run_now(&self.ecs);
 
+        let mut perception = PerceptionSystem {};
+        perception.run_now(&self.ecs);
+
         let mut mob = MonsterAI {};
         mob.run_now(&self.ecs);
 
         let mut map_index = MapIndexingSystem {};
         map_index.run_now(&self.ecs);
 
+        let mut traps = TrapSystem {};
+        traps.run_now(&self.ecs);
+
+        let mut awareness = AwarenessSystem {};
+        awareness.run_now(&self.ecs);
+
+        let mut memory = MemorySystem {};
+        memory.run_now(&self.ecs);
+
         let mut melee = MeleeCombatSystem {};
         melee.run_now(&self.ecs);
 
+        let mut spells = SpellSystem {};
+        spells.run_now(&self.ecs);
+
+        let mut hazards = HazardSystem {};
+        hazards.run_now(&self.ecs);
+
+        let mut hunger = HungerSystem {};
+        hunger.run_now(&self.ecs);
+
         let mut damage = DamageSystem {};
         damage.run_now(&self.ecs);
 
+        let mut regen = RegenSystem {};
+        regen.run_now(&self.ecs);
+
         self.ecs.maintain();
+
+        // wading into shallow water costs the player a turn, which
+        // everything else gets to use
+        if player_loses_turn(&mut self.ecs) {
+            self.run_systems();
+        }
     }
 }
 
@@ -63,33 +154,203 @@ impl GameState for State {
     fn tick(&mut self, ctx: &mut Rltk) {
         // clear the screen. Generally do at the beginning of a frame
         ctx.cls();
+        cull_dead_particles(&mut self.ecs, ctx);
 
         // Only the run the simulation when the game isn't paused
-        if self.run_state == RunState::Running {
-            self.run_systems();
-            // pause after each step
-            self.run_state = RunState::Paused;
-        } else {
-            self.run_state = player_input(self, ctx);
+        match self.run_state {
+            RunState::Running => {
+                self.run_systems();
+                // pause after each step
+                self.run_state = RunState::Paused;
+            }
+            RunState::Paused => {
+                self.run_state = player_input(self, ctx);
+            }
+            RunState::LevelUp => {
+                self.run_state = level_up_input(self, ctx);
+            }
+            RunState::SpellMenu => {
+                self.run_state = spell_menu_input(self, ctx);
+            }
+            RunState::Inventory => {
+                self.run_state = inventory_input(self, ctx);
+            }
+            RunState::Targeting { spell, cursor } => {
+                self.run_state = targeting_input(self, ctx, spell, cursor);
+            }
+            RunState::Trade { vendor, selling } => {
+                self.run_state = trade_input(self, ctx, vendor, selling);
+            }
+            RunState::GameOver => {
+                if ctx.key.is_some() {
+                    ctx.quit();
+                }
+            }
+            // any key stops these, or closes the help screen
+            _ if ctx.key.is_some() => {
+                self.run_state = RunState::Paused;
+            }
+            RunState::AutoExplore => {
+                self.run_state = auto_explore_turn(self);
+            }
+            RunState::Travel => {
+                self.run_state = travel_turn(self);
+            }
+            RunState::Run { dx, dy } => {
+                self.run_state = run_turn(self, dx, dy);
+            }
+            RunState::Rest { turns } => {
+                self.run_state = rest_turn(self, turns);
+            }
+            RunState::Help => {}
         }
 
         delete_the_dead(&mut self.ecs);
 
-        draw_map(&self.ecs, ctx);
+        // particles are made every frame, whether or not a turn went by
+        let mut particles = ParticleSpawnSystem {};
+        particles.run_now(&self.ecs);
+        self.ecs.maintain();
+
+        // stop whatever the player was doing to level up, or because they died
+        if player_is_dead(&self.ecs) {
+            self.run_state = RunState::GameOver;
+        } else if self.run_state != RunState::LevelUp && player_can_level_up(&self.ecs) {
+            self.run_state = RunState::LevelUp;
+        }
+
+        // center the view on the player
+        let camera = self.camera();
+        draw_map(&self.ecs, ctx, &camera);
 
         // asks the ECS for read access to where the Position components are stored
         let positions = self.ecs.read_storage::<Position>();
         let renderables = self.ecs.read_storage::<Renderable>();
+        let hidden = self.ecs.read_storage::<Hidden>();
         let map = self.ecs.fetch::<Map>(); // get map from ECS
 
-        // The join call only returns entities that have both
-        for (pos, render) in (&positions, &renderables).join() {
+        // The join call only returns entities that have both, and aren't
+        // hidden. They're drawn in render order, so that creatures stand on
+        // top of items and particles go over everything.
+        let mut entities: Vec<(&Position, &Renderable)> = (&positions, &renderables, !&hidden)
+            .join()
+            .map(|(pos, render, _hidden)| (pos, render))
+            .collect();
+        entities.sort_by_key(|(_pos, render)| render.render_order);
+        for (pos, render) in entities {
             let i = map.idx(pos.x, pos.y); // get current position
             if map.visible_tiles[i] {
-                // render the tile if it's visible
-                ctx.set(pos.x, pos.y, render.fg, render.bg, render.glyph);
+                // render the tile if it's visible and on screen
+                if let Some((x, y)) = camera.world_to_screen(pos.x, pos.y) {
+                    ctx.set(x, y, render.fg, render.bg, render.glyph);
+                }
             }
         }
+
+        gui::draw_sidebar(&self.ecs, ctx, camera.width, SCREEN_HEIGHT);
+
+        match self.run_state {
+            RunState::Help => gui::draw_help(&self.ecs, ctx),
+            RunState::LevelUp => gui::draw_level_up(ctx),
+            RunState::GameOver => gui::draw_game_over(&self.ecs, ctx),
+            RunState::SpellMenu => gui::draw_spell_menu(&self.ecs, ctx),
+            RunState::Inventory => gui::draw_inventory(&self.ecs, ctx),
+            RunState::Trade { vendor, selling } => gui::draw_trade(&self.ecs, ctx, vendor, selling),
+            RunState::Targeting { spell, cursor } => {
+                gui::draw_targeting(ctx, &camera, &spell_targets(&self.ecs, spell), cursor)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Starts building a ration, which can go on the map or in a backpack
+fn ration(ecs: &mut World) -> EntityBuilder<'_> {
+    ecs.create_entity()
+        .with(Renderable {
+            glyph: rltk::to_cp437('%'),
+            fg: RGB::named(rltk::GREEN),
+            bg: RGB::named(rltk::BLACK),
+            render_order: RenderOrder::Item,
+        })
+        .with(Name {
+            name: "Ration".to_string(),
+        })
+        .with(Item {})
+        .with(Value { gold: 10 })
+        .with(ProvidesFood {})
+}
+
+/// Starts building a potion or scroll of the given kind
+fn consumable(ecs: &mut World, kind: ConsumableKind) -> EntityBuilder<'_> {
+    let (glyph, fg) = if kind.is_potion() {
+        ('!', RGB::named(rltk::MAGENTA))
+    } else {
+        ('?', RGB::named(rltk::WHEAT))
+    };
+    ecs.create_entity()
+        .with(Renderable {
+            glyph: rltk::to_cp437(glyph),
+            fg,
+            bg: RGB::named(rltk::BLACK),
+            render_order: RenderOrder::Item,
+        })
+        .with(Name {
+            name: kind.name().to_string(),
+        })
+        .with(Item {})
+        .with(Value { gold: kind.value() })
+        .with(Consumable { kind })
+}
+
+/// Puts a shopkeeper in the middle of the map's shop, if it has one, with
+/// some rations and magic for sale
+fn spawn_shopkeeper(ecs: &mut World, map: &Map) {
+    let shop = match map.shop {
+        None => return,
+        Some(shop) => shop,
+    };
+    let (x, y) = map.rooms[shop].center();
+    let vendor = ecs
+        .create_entity()
+        .with(Position::new(x, y))
+        .with(Renderable {
+            glyph: rltk::to_cp437('@'),
+            fg: RGB::named(rltk::GOLD),
+            bg: RGB::named(rltk::BLACK),
+            render_order: RenderOrder::Creature,
+        })
+        .with(Name {
+            name: "Shopkeeper".to_string(),
+        })
+        .with(Vendor { markup: 50 })
+        .with(Purse { gold: 100 })
+        .with(BlocksTile {})
+        .build();
+    for _ in 0..3 {
+        ration(ecs).with(InBackpack { owner: vendor }).build();
+    }
+    for kind in [
+        ConsumableKind::HealingPotion,
+        ConsumableKind::IdentifyScroll,
+    ]
+    .iter()
+    {
+        consumable(ecs, *kind)
+            .with(InBackpack { owner: vendor })
+            .build();
+    }
+}
+
+/// The seed to start the game with: the first command line argument, or a
+/// random one if there isn't one
+fn game_seed() -> u64 {
+    match std::env::args().nth(1) {
+        Some(arg) => arg.parse().unwrap_or_else(|_| {
+            rltk::console::log(format!("{} isn't a seed. Using a random one.", arg));
+            rltk::RandomNumberGenerator::new().next_u64()
+        }),
+        None => rltk::RandomNumberGenerator::new().next_u64(),
     }
 }
 
@@ -111,14 +372,49 @@ fn main() {
     gs.ecs.register::<Renderable>();
     gs.ecs.register::<Player>();
     gs.ecs.register::<Monster>();
+    gs.ecs.register::<Faction>();
+    gs.ecs.register::<Follower>();
     gs.ecs.register::<Viewshed>();
     gs.ecs.register::<Name>();
     gs.ecs.register::<BlocksTile>();
     gs.ecs.register::<CombatStats>();
     gs.ecs.register::<CanMelee>();
+    gs.ecs.register::<MeleeDamage>();
     gs.ecs.register::<SuffersDamage>();
+    gs.ecs.register::<Swimmer>();
+    gs.ecs.register::<Wading>();
+    gs.ecs.register::<Trap>();
+    gs.ecs.register::<Hidden>();
+    gs.ecs.register::<EntityMoved>();
+    gs.ecs.register::<HeardNoise>();
+    gs.ecs.register::<LightSource>();
+    gs.ecs.register::<Searching>();
+    gs.ecs.register::<HealthRegen>();
+    gs.ecs.register::<HungerClock>();
+    gs.ecs.register::<Item>();
+    gs.ecs.register::<ProvidesFood>();
+    gs.ecs.register::<Consumable>();
+    gs.ecs.register::<InBackpack>();
+    gs.ecs.register::<Purse>();
+    gs.ecs.register::<GoldPile>();
+    gs.ecs.register::<Value>();
+    gs.ecs.register::<Vendor>();
+    gs.ecs.register::<ParticleLifetime>();
+    gs.ecs.register::<Mana>();
+    gs.ecs.register::<KnownSpells>();
+    gs.ecs.register::<WantsToCastSpell>();
+    gs.ecs.register::<Confusion>();
+    gs.ecs.register::<Experience>();
+    gs.ecs.register::<XpReward>();
+    gs.ecs.register::<Resistances>();
+    gs.ecs.register::<LastAttacker>();
 
-    let map: Map = Map::new_map_rooms_and_corridors();
+    // one generator makes the map and everything in it, so a seed replays
+    // the same game
+    let seed = game_seed();
+    rltk::console::log(format!("Seed: {}", seed));
+    let mut rng = rltk::RandomNumberGenerator::seeded(seed);
+    let map: Map = Map::new_map_rooms_and_corridors(MAP_WIDTH, MAP_HEIGHT, &mut rng);
 
     // Now we can create entities with positions that can also be drawn on the screen
     let (player_x, player_y) = map.rooms[0].center();
@@ -129,63 +425,243 @@ fn main() {
             glyph: rltk::to_cp437('@'),
             fg: RGB::named(rltk::YELLOW),
             bg: RGB::named(rltk::BLACK),
+            render_order: RenderOrder::Creature,
         })
         .with(Player {})
+        .with(Faction {
+            id: FactionId::Player,
+        })
         .with(Viewshed {
             visible_tiles: Vec::new(),
             range: 8,
             dirty: true,
+            algorithm: FovAlgorithm::SymmetricShadowcasting,
         })
         .with(Name {
             name: "Hawk Darkstone".to_string(),
         })
         .with(CombatStats::new(30, 30, 2, 5))
+        .with(MeleeDamage {
+            dice: rltk::DiceType::new(1, 6, 2),
+        })
+        .with(Experience { level: 1, xp: 0 })
+        .with(Purse { gold: 20 })
+        .with(HealthRegen::new(10))
+        .with(Mana::new(10, 4))
+        .with(KnownSpells {
+            spells: vec![
+                Spell::MagicMissile,
+                Spell::Heal,
+                Spell::Blink,
+                Spell::Confuse,
+                Spell::CallWolf,
+            ],
+        })
+        .with(HungerClock::well_fed())
+        .with(LightSource {
+            radius: 6,
+            colour: RGB::from_f32(1.0, 0.85, 0.6), // torchlight
+        })
         .build();
 
     // Add a monster to the center of each room
     // skip first room b/c that's where the player spawns
-    // add two types of monsters
-    let mut rng = rltk::RandomNumberGenerator::new();
-    for (i, room) in map.rooms.iter().skip(1).enumerate() {
+    // add three types of monsters
+    for (i, (_, room)) in map.wild_rooms().enumerate() {
         let (x, y) = room.center();
 
         let glyph: u8;
         let name: String;
-        let roll = rng.roll_dice(1, 2);
+        let xp: i32;
+        let resistances: Resistances;
+        let damage: rltk::DiceType;
+        let mut fg = RGB::named(rltk::RED);
+        let mut spells: Vec<Spell> = Vec::new();
+        let faction: FactionId;
+        let roll = rng.roll_dice(1, 3);
         match roll {
             1 => {
                 glyph = rltk::to_cp437('g');
                 name = "Goblin".to_string();
+                xp = 30;
+                faction = FactionId::Goblins;
+                damage = rltk::DiceType::new(1, 4, 1);
+                // goblins live on scraps
+                resistances = Resistances {
+                    resists: vec![DamageType::Poison],
+                    ..Resistances::default()
+                };
+            }
+            2 => {
+                glyph = rltk::to_cp437('g');
+                fg = RGB::named(rltk::MAGENTA);
+                name = "Goblin Shaman".to_string();
+                xp = 50;
+                faction = FactionId::Goblins;
+                damage = rltk::DiceType::new(1, 3, 0);
+                resistances = Resistances {
+                    resists: vec![DamageType::Poison, DamageType::Magic],
+                    ..Resistances::default()
+                };
+                spells = vec![Spell::MagicMissile, Spell::Heal];
             }
             _ => {
                 glyph = rltk::to_cp437('o');
                 name = "Orc".to_string();
+                xp = 40;
+                faction = FactionId::Orcs;
+                damage = rltk::DiceType::new(1, 6, 1);
+                // orcs grease their hair
+                resistances = Resistances {
+                    vulnerable_to: vec![DamageType::Fire],
+                    ..Resistances::default()
+                };
             }
         }
 
-        gs.ecs
+        let monster = gs
+            .ecs
             .create_entity()
             .with(Position::new(x, y))
             .with(Renderable {
-                glyph: glyph,
-                fg: RGB::named(rltk::RED),
+                glyph,
+                fg,
                 bg: RGB::named(rltk::BLACK),
+                render_order: RenderOrder::Creature,
             })
             .with(Viewshed {
                 visible_tiles: Vec::new(),
                 range: 8,
                 dirty: true,
+                // everything uses the same algorithm as the player, so if a
+                // monster can see the player, the player can see it too
+                algorithm: FovAlgorithm::SymmetricShadowcasting,
             })
             .with(Monster {})
+            .with(Faction { id: faction })
             .with(Name {
                 name: format!("{} #{}", &name, i),
             })
             .with(CombatStats::new(16, 16, 1, 4))
+            .with(MeleeDamage { dice: damage })
+            .with(XpReward { xp })
+            .with(resistances)
             .with(BlocksTile {})
             .build();
+
+        if !spells.is_empty() {
+            let mut mana = gs.ecs.write_storage::<Mana>();
+            mana.insert(monster, Mana::new(8, 3))
+                .expect("Unable to insert mana.");
+            let mut known_spells = gs.ecs.write_storage::<KnownSpells>();
+            known_spells
+                .insert(monster, KnownSpells { spells })
+                .expect("Unable to insert spells.");
+        }
+
+        // most monsters carry a little gold
+        if rng.roll_dice(1, 3) > 1 {
+            let gold = rng.roll_dice(2, 6);
+            gs.ecs
+                .write_storage::<Purse>()
+                .insert(monster, Purse { gold })
+                .expect("Unable to insert purse.");
+        }
+
+        // some monsters carry a snack
+        if rng.roll_dice(1, 3) == 1 {
+            ration(&mut gs.ecs)
+                .with(InBackpack { owner: monster })
+                .build();
+        }
     }
 
+    // Some rooms get a hidden trap, somewhere other than the middle
+    for (_, room) in map.wild_rooms() {
+        if rng.roll_dice(1, 3) != 1 {
+            continue;
+        }
+
+        let x = rng.range(room.x1 + 1, room.x2 + 1);
+        let y = rng.range(room.y1 + 1, room.y2 + 1);
+        if (x, y) == room.center() || map[(x, y)] != TileType::Floor {
+            continue;
+        }
+
+        let (kind, name, rearms) = match rng.roll_dice(1, 3) {
+            1 => (TrapKind::Spike, "spike trap", true),
+            2 => (TrapKind::Teleport, "teleport trap", false),
+            _ => (TrapKind::Alarm, "alarm trap", false),
+        };
+
+        gs.ecs
+            .create_entity()
+            .with(Position::new(x, y))
+            .with(Renderable {
+                glyph: rltk::to_cp437('^'),
+                fg: RGB::named(rltk::ORANGE),
+                bg: RGB::named(rltk::BLACK),
+                render_order: RenderOrder::Item,
+            })
+            .with(Name {
+                name: name.to_string(),
+            })
+            .with(Trap { kind, rearms })
+            .with(Hidden {})
+            .build();
+    }
+
+    // and some have a ration lying around
+    for (_, room) in map.wild_rooms() {
+        if rng.roll_dice(1, 4) != 1 {
+            continue;
+        }
+
+        let x = rng.range(room.x1 + 1, room.x2 + 1);
+        let y = rng.range(room.y1 + 1, room.y2 + 1);
+        if (x, y) == room.center() || map[(x, y)] != TileType::Floor {
+            continue;
+        }
+
+        ration(&mut gs.ecs).with(Position::new(x, y)).build();
+    }
+
+    // and some have a potion or scroll
+    for (_, room) in map.wild_rooms() {
+        if rng.roll_dice(1, 3) != 1 {
+            continue;
+        }
+
+        let x = rng.range(room.x1 + 1, room.x2 + 1);
+        let y = rng.range(room.y1 + 1, room.y2 + 1);
+        if (x, y) == room.center() || map[(x, y)] != TileType::Floor {
+            continue;
+        }
+
+        let kind = ConsumableKind::ALL[rng.range(0, ConsumableKind::ALL.len())];
+        consumable(&mut gs.ecs, kind)
+            .with(Position::new(x, y))
+            .build();
+    }
+
+    // and the shop gets a shopkeeper
+    spawn_shopkeeper(&mut gs.ecs, &map);
+
+    // what the potions and scrolls look like is different every game
+    gs.ecs.insert(KnownItems::new(&mut rng));
     gs.ecs.insert(map);
+    gs.ecs.insert(rng);
+    gs.ecs.insert(VisibleMonsters::default());
+    gs.ecs.insert(EntityMemory::default());
+    gs.ecs.insert(TravelPath::default());
+    gs.ecs.insert(ParticleBuilder::default());
+    gs.ecs.insert(Reactions::default());
+
+    let keymap = Keymap::load().unwrap_or_else(|e| {
+        rltk::console::log(format!("{}. Using the WASD keys instead.", e));
+        Keymap::from_preset(Preset::Wasd)
+    });
+    gs.ecs.insert(keymap);
     // the ECS will be able to track the player's position
     gs.ecs.insert(Point::new(player_x, player_y));
 
@@ -1,4 +1,6 @@
 // extern crate rltk;
+use super::camera::Camera;
+use super::systems::memory_system::{EntityMemory, Ghost};
 use super::Rect;
 use rltk::{Algorithm2D, BaseMap, Console, DistanceAlg, Point, RandomNumberGenerator, Rltk, RGB};
 use specs::prelude::*;
@@ -10,6 +12,70 @@ use std::ops::{Index, IndexMut};
 pub enum TileType {
     Wall,
     Floor,
+    ShallowWater,
+    DeepWater,
+    Lava,
+    Chasm,
+    Bridge,
+}
+
+impl TileType {
+    /// Returns true if this tile blocks line of sight
+    pub fn is_opaque(self) -> bool {
+        self == TileType::Wall
+    }
+
+    /// Returns true if nothing can walk onto this tile
+    pub fn blocks_walking(self) -> bool {
+        matches!(self, TileType::Wall | TileType::Chasm)
+    }
+
+    /// Returns true if only swimmers can enter this tile
+    pub fn needs_swimming(self) -> bool {
+        self == TileType::DeepWater
+    }
+
+    /// How much it costs to step onto this tile, relative to plain floor.
+    /// Pathfinding uses this, so monsters avoid slow or dangerous tiles.
+    pub fn move_cost(self) -> f32 {
+        match self {
+            TileType::ShallowWater => 2.0,
+            TileType::Lava => 20.0,
+            _ => 1.0,
+        }
+    }
+
+    /// The damage done to anything that enters, or stays on, this tile
+    pub fn damage_on_enter(self) -> Option<i32> {
+        match self {
+            TileType::Lava => Some(10),
+            _ => None,
+        }
+    }
+}
+
+/// Marks left on the ground, drawn as a background colour under whatever is
+/// on the tile
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Decal {
+    Blood,
+    Scorch,
+    Footprints,
+}
+
+impl Decal {
+    pub fn colour(self) -> RGB {
+        match self {
+            Decal::Blood => RGB::from_f32(0.4, 0.0, 0.0),
+            Decal::Scorch => RGB::from_f32(0.2, 0.15, 0.1),
+            Decal::Footprints => RGB::from_f32(0.25, 0.05, 0.05),
+        }
+    }
+
+    /// Faint marks like footprints don't cover up anything stronger
+    fn is_faint(self) -> bool {
+        self == Decal::Footprints
+    }
 }
 
 /// Map
@@ -30,8 +96,35 @@ pub struct Map {
     pub blocked: Vec<bool>,
     // track what is on each tile
     pub tile_content: Vec<Vec<Entity>>,
+    // tiles that are lit without needing a light source, like lit rooms
+    pub lit_tiles: Vec<bool>,
+    // the colour and brightness of the light falling on each tile
+    // black means the tile is dark and can't be seen
+    pub light: Vec<RGB>,
+    // set when the light changed since the last turn
+    pub light_changed: bool,
+    // the room with a shop in it, as an index into `rooms`
+    pub shop: Option<usize>,
+    // marks left on each tile, if any
+    pub decals: Vec<Option<Decal>>,
+    // set when a tile changed in a way that could change what can be seen,
+    // so every viewshed has to be recomputed
+    pub view_changed: bool,
 }
 
+/// The eight neighbouring offsets of a tile and the cost of stepping to each.
+/// Cardinal directions come first, then diagonals.
+const NEIGHBOURS: [(i32, i32, f32); 8] = [
+    (-1, 0, 1.0),
+    (1, 0, 1.0),
+    (0, -1, 1.0),
+    (0, 1, 1.0),
+    (-1, -1, 1.45),
+    (1, -1, 1.45),
+    (-1, 1, 1.45),
+    (1, 1, 1.45),
+];
+
 // These let me use subscripting
 impl Index<(i32, i32)> for Map {
     type Output = TileType;
@@ -39,8 +132,7 @@ impl Index<(i32, i32)> for Map {
     #[inline]
     fn index(&self, (x, y): (i32, i32)) -> &TileType {
         assert!(self.contains(x, y), "Map position out of bounds.");
-        let idx = (y as usize * self.width as usize) + x as usize;
-        &self.tiles[idx]
+        &self.tiles[self.idx(x, y)]
     }
 }
 
@@ -48,20 +140,115 @@ impl IndexMut<(i32, i32)> for Map {
     #[inline]
     fn index_mut(&mut self, (x, y): (i32, i32)) -> &mut TileType {
         assert!(self.contains(x, y), "Map position out of bounds.");
-        let idx = (y as usize * self.width as usize) + x as usize;
+        let idx = self.idx(x, y);
         &mut self.tiles[idx]
     }
 }
 
+impl Index<Point> for Map {
+    type Output = TileType;
+
+    #[inline]
+    fn index(&self, p: Point) -> &TileType {
+        &self[(p.x, p.y)]
+    }
+}
+
+impl IndexMut<Point> for Map {
+    #[inline]
+    fn index_mut(&mut self, p: Point) -> &mut TileType {
+        &mut self[(p.x, p.y)]
+    }
+}
+
 impl Map {
+    /// Returns true if (x,y) is on the map, including the outer edge
     pub fn contains(&self, x: i32, y: i32) -> bool {
-        x > 0 && x < self.width && y > 0 && y < self.height
+        x >= 0 && x < self.width && y >= 0 && y < self.height
+    }
+
+    pub fn contains_point(&self, p: Point) -> bool {
+        self.contains(p.x, p.y)
+    }
+
+    /// Returns true if (x,y) is on the map but not on its outer edge. The
+    /// edge is always wall, so only interior tiles can be dug out.
+    pub fn is_interior(&self, x: i32, y: i32) -> bool {
+        x > 0 && x < self.width - 1 && y > 0 && y < self.height - 1
     }
 
+    /// Converts (x,y) to an index into the tile vectors. The position must be
+    /// on the map.
     pub fn idx(&self, x: i32, y: i32) -> usize {
+        debug_assert!(self.contains(x, y), "Map position out of bounds.");
         (y as usize * self.width as usize) + x as usize
     }
 
+    pub fn point_idx(&self, p: Point) -> usize {
+        self.idx(p.x, p.y)
+    }
+
+    /// Converts an index into the tile vectors back to a position
+    pub fn idx_point(&self, idx: usize) -> Point {
+        let w = self.width as usize;
+        Point::new(idx % w, idx / w)
+    }
+
+    /// Iterates over the neighbours of (x,y) that are on the map, along with
+    /// the cost of moving to each one
+    pub fn neighbours(&self, x: i32, y: i32) -> impl Iterator<Item = (Point, f32)> + '_ {
+        NEIGHBOURS
+            .iter()
+            .map(move |&(dx, dy, cost)| (Point::new(x + dx, y + dy), cost))
+            .filter(move |(p, _)| self.contains_point(*p))
+    }
+
+    /// Changes the tile at (x,y). Use this rather than writing to `tiles`
+    /// when the map changes during play (doors, digging), so that viewsheds
+    /// get recomputed.
+    pub fn set_tile(&mut self, x: i32, y: i32, tile: TileType) {
+        let i = self.idx(x, y);
+        if self.tiles[i].is_opaque() != tile.is_opaque() {
+            self.view_changed = true;
+        }
+        self.tiles[i] = tile;
+    }
+
+    /// The rooms monsters and loot can be put in: all but the player's room
+    /// and the shop
+    pub fn wild_rooms(&self) -> impl Iterator<Item = (usize, &Rect)> + '_ {
+        self.rooms
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter(move |(i, _)| Some(*i) != self.shop)
+    }
+
+    /// Picks a random unoccupied floor tile, giving up after a while
+    pub fn random_floor_tile(&self, rng: &mut RandomNumberGenerator) -> Option<Point> {
+        for _ in 0..100 {
+            let x = rng.range(1, self.width - 1);
+            let y = rng.range(1, self.height - 1);
+            let i = self.idx(x, y);
+            if self.tiles[i] == TileType::Floor && !self.blocked[i] {
+                return Some(Point::new(x, y));
+            }
+        }
+        None
+    }
+
+    /// Leaves a mark on the tile at `idx`. Only dry ground takes marks, and
+    /// faint ones don't replace anything already there.
+    pub fn add_decal(&mut self, idx: usize, decal: Decal) {
+        if !matches!(self.tiles[idx], TileType::Floor | TileType::Bridge) {
+            return;
+        }
+        if decal.is_faint() && self.decals[idx].is_some() {
+            return;
+        }
+        self.decals[idx] = Some(decal);
+    }
+
     // Given a room, update the tiles to be floor and not walls
     fn apply_room_to_map(&mut self, room: &Rect) {
         for y in (room.y1 + 1)..=room.y2 {
@@ -71,26 +258,100 @@ impl Map {
         }
     }
 
-    fn add_horizontal_tunnel(&mut self, x1: i32, x2: i32, y: i32) {
+    // Lights the room and its walls
+    fn light_room(&mut self, room: &Rect) {
+        for y in room.y1..=(room.y2 + 1) {
+            for x in room.x1..=(room.x2 + 1) {
+                if self.contains(x, y) {
+                    let i = self.idx(x, y);
+                    self.lit_tiles[i] = true;
+                }
+            }
+        }
+    }
+
+    /// Returns true if there is any light falling on the tile
+    pub fn is_lit(&self, idx: usize) -> bool {
+        let light = self.light[idx];
+        light.r > 0.0 || light.g > 0.0 || light.b > 0.0
+    }
+
+    // Tunnel tiles are recorded in `corridors` so that terrain features never
+    // cut the path between two rooms
+    fn add_horizontal_tunnel(&mut self, corridors: &mut [bool], x1: i32, x2: i32, y: i32) {
         for x in min(x1, x2)..=max(x1, x2) {
-            if self.contains(x, y) {
-                self[(x, y)] = TileType::Floor;
+            if self.is_interior(x, y) {
+                self.set_tile(x, y, TileType::Floor);
+                corridors[self.idx(x, y)] = true;
             }
         }
     }
 
-    fn add_vertical_tunnel(&mut self, y1: i32, y2: i32, x: i32) {
+    fn add_vertical_tunnel(&mut self, corridors: &mut [bool], y1: i32, y2: i32, x: i32) {
         for y in min(y1, y2)..=max(y1, y2) {
-            if self.contains(x, y) {
-                self[(x, y)] = TileType::Floor;
+            if self.is_interior(x, y) {
+                self.set_tile(x, y, TileType::Floor);
+                corridors[self.idx(x, y)] = true;
+            }
+        }
+    }
+
+    /// Adds a pool of water to the room: deep in the middle, shallow around
+    /// the edge. Corridors through the pool stay wadeable.
+    fn add_pool(&mut self, corridors: &[bool], room: &Rect) {
+        for y in (room.y1 + 1)..=room.y2 {
+            for x in (room.x1 + 1)..=room.x2 {
+                let i = self.idx(x, y);
+                let edge =
+                    x <= room.x1 + 2 || x >= room.x2 - 1 || y <= room.y1 + 2 || y >= room.y2 - 1;
+                self.tiles[i] = if edge || corridors[i] {
+                    TileType::ShallowWater
+                } else {
+                    TileType::DeepWater
+                };
+            }
+        }
+    }
+
+    /// Adds a small lava pool in one corner of the room, away from corridors
+    fn add_lava(&mut self, rng: &mut RandomNumberGenerator, corridors: &[bool], room: &Rect) {
+        let x = if rng.range(0, 2) == 1 {
+            room.x1 + 1
+        } else {
+            room.x2 - 2
+        };
+        let y = if rng.range(0, 2) == 1 {
+            room.y1 + 1
+        } else {
+            room.y2 - 2
+        };
+        for y in y..y + 2 {
+            for x in x..x + 2 {
+                let i = self.idx(x, y);
+                if !corridors[i] {
+                    self.tiles[i] = TileType::Lava;
+                }
             }
         }
     }
 
-    /// Sets each tile that is a wall tile to blocked
+    /// Splits the room with a chasm, bridged wherever a corridor crosses it
+    fn add_chasm(&mut self, corridors: &[bool], room: &Rect) {
+        let x = room.x1 + 2;
+        for y in (room.y1 + 1)..=room.y2 {
+            let i = self.idx(x, y);
+            self.tiles[i] = if corridors[i] {
+                TileType::Bridge
+            } else {
+                TileType::Chasm
+            };
+        }
+    }
+
+    /// Sets each tile that can't be walked on to blocked
     pub fn populate_blocked(&mut self) {
-        for (i, tile) in self.tiles.iter_mut().enumerate() {
-            self.blocked[i] = *tile == TileType::Wall;
+        for (i, tile) in self.tiles.iter().enumerate() {
+            self.blocked[i] = tile.blocks_walking();
         }
     }
 
@@ -101,6 +362,17 @@ impl Map {
         }
     }
 
+    /// Returns true if (x,y) is terrain that can be walked on without
+    /// swimming. Unlike `blocked`, this ignores anything standing there.
+    pub fn is_walkable(&self, x: i32, y: i32) -> bool {
+        if !self.contains(x, y) {
+            return false;
+        }
+
+        let tile = self[(x, y)];
+        !tile.blocks_walking() && !tile.needs_swimming()
+    }
+
     /// Returns true if (x,y) is an exit from the room
     fn is_exit_valid(&self, x: i32, y: i32) -> bool {
         if !self.contains(x, y) {
@@ -108,26 +380,42 @@ impl Map {
         }
 
         let i = self.idx(x, y);
-        !self.blocked[i]
+        !self.blocked[i] && !self.tiles[i].needs_swimming()
     }
 
-    pub fn new_map_rooms_and_corridors() -> Map {
-        let mut map = Map {
-            tiles: vec![TileType::Wall; 80 * 50],
+    /// Creates a map of the given size that is entirely walls
+    pub fn new(width: i32, height: i32) -> Map {
+        let size = (width * height) as usize;
+        Map {
+            tiles: vec![TileType::Wall; size],
             rooms: Vec::new(),
-            width: 80,
-            height: 50,
-            revealed_tiles: vec![false; 80 * 50],
-            visible_tiles: vec![false; 80 * 50],
-            blocked: vec![false; 80 * 50],
-            tile_content: vec![Vec::new(); 80 * 50],
-        };
+            width,
+            height,
+            revealed_tiles: vec![false; size],
+            visible_tiles: vec![false; size],
+            blocked: vec![false; size],
+            tile_content: vec![Vec::new(); size],
+            lit_tiles: vec![false; size],
+            light: vec![RGB::from_f32(0., 0., 0.); size],
+            light_changed: false,
+            view_changed: false,
+            decals: vec![None; size],
+            shop: None,
+        }
+    }
+
+    pub fn new_map_rooms_and_corridors(
+        width: i32,
+        height: i32,
+        rng: &mut RandomNumberGenerator,
+    ) -> Map {
+        let mut map = Map::new(width, height);
 
         const MAX_ROOMS: i32 = 30;
         const MIN_SIZE: i32 = 6;
         const MAX_SIZE: i32 = 10;
 
-        let mut rng = RandomNumberGenerator::new();
+        let mut corridors = vec![false; map.tiles.len()];
 
         for _ in 0..MAX_ROOMS {
             let w = rng.range(MIN_SIZE, MAX_SIZE);
@@ -147,17 +435,22 @@ impl Map {
             if ok {
                 map.apply_room_to_map(&new_room);
 
+                // The player's room is always lit; others are lit 2/3 of the time
+                if map.rooms.is_empty() || rng.roll_dice(1, 3) > 1 {
+                    map.light_room(&new_room);
+                }
+
                 if !map.rooms.is_empty() {
                     let (new_x, new_y) = new_room.center();
                     let (prev_x, prev_y) = map.rooms[map.rooms.len() - 1].center();
 
                     // 50% chance of order to draw tunnels
                     if rng.range(0, 2) == 1 {
-                        map.add_horizontal_tunnel(prev_x, new_x, prev_y);
-                        map.add_vertical_tunnel(prev_y, new_y, new_x);
+                        map.add_horizontal_tunnel(&mut corridors, prev_x, new_x, prev_y);
+                        map.add_vertical_tunnel(&mut corridors, prev_y, new_y, new_x);
                     } else {
-                        map.add_vertical_tunnel(prev_y, new_y, prev_x);
-                        map.add_horizontal_tunnel(prev_x, new_x, new_y);
+                        map.add_vertical_tunnel(&mut corridors, prev_y, new_y, prev_x);
+                        map.add_horizontal_tunnel(&mut corridors, prev_x, new_x, new_y);
                     }
                 }
 
@@ -165,6 +458,34 @@ impl Map {
             }
         }
 
+        // The last room is a shop, as long as it isn't the player's room.
+        // Shops are kept well lit.
+        if map.rooms.len() > 2 {
+            let shop = map.rooms.len() - 1;
+            let room = map.rooms[shop].clone();
+            map.light_room(&room);
+            map.shop = Some(shop);
+        }
+
+        // Decorate some rooms with terrain, but leave the player's room and
+        // the shop alone
+        let rooms: Vec<Rect> = map
+            .rooms
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter(|(i, _)| Some(*i) != map.shop)
+            .map(|(_, room)| room.clone())
+            .collect();
+        for room in rooms.iter() {
+            match rng.roll_dice(1, 6) {
+                1 => map.add_pool(&corridors, room),
+                2 => map.add_lava(rng, &corridors, room),
+                3 => map.add_chasm(&corridors, room),
+                _ => {}
+            }
+        }
+
         map
     }
 }
@@ -178,88 +499,267 @@ impl Algorithm2D for Map {
 impl BaseMap for Map {
     // This suggests it's possible to have things that can temporarily modify the opacity of a wall!
     fn is_opaque(&self, idx: usize) -> bool {
-        self.tiles[idx as usize] == TileType::Wall
+        self.tiles[idx].is_opaque()
     }
 
     fn get_available_exits(&self, idx: usize) -> Vec<(usize, f32)> {
-        let mut exits: Vec<(i32, f32)> = Vec::new();
-        let i = idx as i32;
-        let x = i % self.width;
-        let y = i / self.width;
-
-        // Cardinal directions
-        if self.is_exit_valid(x - 1, y) {
-            exits.push((i - 1, 1.0))
-        };
-        if self.is_exit_valid(x + 1, y) {
-            exits.push((i + 1, 1.0))
-        };
-        if self.is_exit_valid(x, y - 1) {
-            exits.push((i - self.width, 1.0))
-        };
-        if self.is_exit_valid(x, y + 1) {
-            exits.push((i + self.width, 1.0))
-        };
-
-        // Diagonals
-        if self.is_exit_valid(x - 1, y - 1) {
-            exits.push(((i - self.width) - 1, 1.45));
-        }
-        if self.is_exit_valid(x + 1, y - 1) {
-            exits.push(((i - self.width) + 1, 1.45));
-        }
-        if self.is_exit_valid(x - 1, y + 1) {
-            exits.push(((i + self.width) - 1, 1.45));
-        }
-        if self.is_exit_valid(x + 1, y + 1) {
-            exits.push(((i + self.width) + 1, 1.45));
-        }
-        // this is a hack to address an issue in the tutorial code not matching BaseMap
-        let exits2: Vec<(usize, f32)> = exits.iter().map(|(a, b)| (*a as usize, *b)).collect();
-        exits2
+        let p = self.idx_point(idx);
+        self.neighbours(p.x, p.y)
+            .filter(|(n, _)| self.is_exit_valid(n.x, n.y))
+            .map(|(n, cost)| {
+                let i = self.point_idx(n);
+                (i, cost * self.tiles[i].move_cost())
+            })
+            .collect()
     }
 
     fn get_pathing_distance(&self, idx1: usize, idx2: usize) -> f32 {
-        let w = self.width as usize;
-        let p1 = Point::new(idx1 % w, idx1 / w);
-        let p2 = Point::new(idx2 % w, idx2 / w);
+        let p1 = self.idx_point(idx1);
+        let p2 = self.idx_point(idx2);
         DistanceAlg::Pythagoras.distance2d(p1, p2)
     }
 }
 
-pub fn draw_map(ecs: &World, ctx: &mut Rltk) {
+/// Draws the part of the map that the camera can see. Anything off the edge
+/// of the map is drawn as blank space. Things the player remembers seeing are
+/// drawn greyed out, like remembered terrain.
+pub fn draw_map(ecs: &World, ctx: &mut Rltk, camera: &Camera) {
     let map = ecs.fetch::<Map>();
+    let memory = ecs.fetch::<EntityMemory>();
+
+    for (sy, y) in (camera.min_y..camera.max_y()).enumerate() {
+        for (sx, x) in (camera.min_x..camera.max_x()).enumerate() {
+            let (sx, sy) = (sx as i32, sy as i32);
+            if !map.contains(x, y) {
+                ctx.set(
+                    sx,
+                    sy,
+                    RGB::named(rltk::BLACK),
+                    RGB::named(rltk::BLACK),
+                    rltk::to_cp437(' '),
+                );
+                continue;
+            }
 
-    let mut y = 0;
-    let mut x = 0;
-
-    for (i, tile) in map.tiles.iter().enumerate() {
-        // Render a tile depending on tile type
-        if map.revealed_tiles[i] {
-            let glyph;
-            let mut fg;
+            // Render a tile depending on tile type
+            let i = map.idx(x, y);
+            if map.revealed_tiles[i] {
+                let glyph;
+                let mut fg;
 
-            match tile {
-                TileType::Floor => {
-                    glyph = rltk::to_cp437('.');
-                    fg = RGB::from_f32(0.0, 0.5, 0.5);
+                match map.tiles[i] {
+                    TileType::Floor => {
+                        glyph = rltk::to_cp437('.');
+                        fg = RGB::from_f32(0.0, 0.5, 0.5);
+                    }
+                    TileType::Wall => {
+                        glyph = rltk::to_cp437('#');
+                        fg = RGB::from_f32(0., 1., 0.);
+                    }
+                    TileType::ShallowWater => {
+                        glyph = rltk::to_cp437('~');
+                        fg = RGB::from_f32(0.3, 0.6, 1.0);
+                    }
+                    TileType::DeepWater => {
+                        glyph = rltk::to_cp437('~');
+                        fg = RGB::from_f32(0.0, 0.0, 0.8);
+                    }
+                    TileType::Lava => {
+                        glyph = rltk::to_cp437('~');
+                        fg = RGB::from_f32(1.0, 0.4, 0.0);
+                    }
+                    TileType::Chasm => {
+                        glyph = rltk::to_cp437(':');
+                        fg = RGB::from_f32(0.3, 0.3, 0.3);
+                    }
+                    TileType::Bridge => {
+                        glyph = rltk::to_cp437('=');
+                        fg = RGB::from_f32(0.6, 0.4, 0.2);
+                    }
                 }
-                TileType::Wall => {
-                    glyph = rltk::to_cp437('#');
-                    fg = RGB::from_f32(0., 1., 0.);
+
+                let mut bg = map.decals[i].map_or(RGB::from_f32(0., 0., 0.), Decal::colour);
+
+                if map.visible_tiles[i] {
+                    // tint by whatever light is falling on the tile
+                    fg = fg * map.light[i];
+                    bg = bg * map.light[i];
+                } else {
+                    fg = fg.to_greyscale();
+                    bg = bg.to_greyscale();
                 }
+                ctx.set(sx, sy, fg, bg, glyph);
             }
+        }
+    }
+
+    // anything on a visible tile is drawn as itself instead. A remembered
+    // monster is drawn over a remembered corpse or item on the same tile.
+    let mut ghosts: Vec<&Ghost> = memory.ghosts.values().collect();
+    ghosts.sort_by_key(|ghost| ghost.render_order);
+    for ghost in ghosts {
+        if map.visible_tiles[map.idx(ghost.x, ghost.y)] {
+            continue;
+        }
+        if let Some((sx, sy)) = camera.world_to_screen(ghost.x, ghost.y) {
+            let fg = ghost.fg.to_greyscale();
+            ctx.set(sx, sy, fg, RGB::from_f32(0., 0., 0.), ghost.glyph);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // deliberately not the default size, and not square
+    fn test_map() -> Map {
+        Map::new(13, 7)
+    }
+
+    #[test]
+    fn contains_includes_edges_and_corners() {
+        let map = test_map();
+        for &(x, y) in &[(0, 0), (12, 0), (0, 6), (12, 6), (5, 0), (0, 3)] {
+            assert!(map.contains(x, y), "({}, {}) should be on the map", x, y);
+        }
+    }
+
+    #[test]
+    fn contains_excludes_outside() {
+        let map = test_map();
+        for &(x, y) in &[(-1, 0), (0, -1), (13, 0), (0, 7), (13, 7), (-1, -1)] {
+            assert!(!map.contains(x, y), "({}, {}) should be off the map", x, y);
+        }
+    }
+
+    #[test]
+    fn interior_excludes_edges() {
+        let map = test_map();
+        assert!(map.is_interior(1, 1));
+        assert!(map.is_interior(11, 5));
+        assert!(!map.is_interior(0, 3));
+        assert!(!map.is_interior(12, 3));
+        assert!(!map.is_interior(5, 0));
+        assert!(!map.is_interior(5, 6));
+    }
 
-            if !map.visible_tiles[i] {
-                fg = fg.to_greyscale()
+    #[test]
+    fn index_round_trips() {
+        let map = test_map();
+        for y in 0..map.height {
+            for x in 0..map.width {
+                let i = map.idx(x, y);
+                assert!(i < map.tiles.len());
+                assert_eq!(map.idx_point(i), Point::new(x, y));
             }
-            ctx.set(x, y, fg, RGB::from_f32(0., 0., 0.), glyph);
         }
+        assert_eq!(map.idx(12, 6), map.tiles.len() - 1);
+    }
+
+    #[test]
+    fn index_by_tuple_and_point_agree() {
+        let mut map = test_map();
+        map[(12, 6)] = TileType::Floor;
+        assert!(map[Point::new(12, 6)] == TileType::Floor);
+        map[Point::new(0, 0)] = TileType::Floor;
+        assert!(map[(0, 0)] == TileType::Floor);
+    }
+
+    #[test]
+    #[should_panic]
+    fn index_out_of_bounds_panics() {
+        let map = test_map();
+        let _ = map[(13, 0)];
+    }
 
-        x += 1;
-        if x > 79 {
-            x = 0;
-            y += 1;
+    #[test]
+    fn neighbour_counts() {
+        let map = test_map();
+        assert_eq!(map.neighbours(0, 0).count(), 3);
+        assert_eq!(map.neighbours(12, 6).count(), 3);
+        assert_eq!(map.neighbours(5, 0).count(), 5);
+        assert_eq!(map.neighbours(0, 3).count(), 5);
+        assert_eq!(map.neighbours(5, 3).count(), 8);
+    }
+
+    #[test]
+    fn exits_skip_blocked_tiles() {
+        let mut map = test_map();
+        let (a, b) = (map.idx(2, 1), map.idx(1, 1));
+        map.blocked[a] = true;
+        map.blocked[b] = true;
+        let exits = map.get_available_exits(map.idx(1, 2));
+        assert_eq!(exits.len(), 6);
+        assert!(exits.iter().all(|(i, _)| !map.blocked[*i]));
+
+        let corner = map.get_available_exits(map.idx(0, 0));
+        assert_eq!(corner.len(), 2);
+    }
+
+    #[test]
+    fn diagonal_exits_cost_more() {
+        let map = test_map();
+        let exits = map.get_available_exits(map.idx(5, 3));
+        let diagonal = exits.iter().find(|(i, _)| *i == map.idx(6, 4)).unwrap();
+        let cardinal = exits.iter().find(|(i, _)| *i == map.idx(6, 3)).unwrap();
+        assert!(diagonal.1 > cardinal.1);
+    }
+
+    #[test]
+    fn hazards_cost_more_and_deep_water_is_not_an_exit() {
+        let mut map = test_map();
+        for x in 1..12 {
+            map[(x, 3)] = TileType::Floor;
+        }
+        map[(4, 3)] = TileType::ShallowWater;
+        map[(6, 3)] = TileType::DeepWater;
+        map.populate_blocked();
+
+        let exits = map.get_available_exits(map.idx(5, 3));
+        let shallow = exits.iter().find(|(i, _)| *i == map.idx(4, 3)).unwrap();
+        assert!((shallow.1 - 2.0).abs() < f32::EPSILON);
+        assert!(exits.iter().all(|(i, _)| *i != map.idx(6, 3)));
+    }
+
+    #[test]
+    fn generated_map_keeps_outer_wall() {
+        let map = Map::new_map_rooms_and_corridors(60, 40, &mut RandomNumberGenerator::seeded(3));
+        for x in 0..map.width {
+            assert!(map[(x, 0)] == TileType::Wall);
+            assert!(map[(x, map.height - 1)] == TileType::Wall);
+        }
+        for y in 0..map.height {
+            assert!(map[(0, y)] == TileType::Wall);
+            assert!(map[(map.width - 1, y)] == TileType::Wall);
         }
     }
+
+    #[test]
+    fn the_same_seed_makes_the_same_map() {
+        let a = Map::new_map_rooms_and_corridors(60, 40, &mut RandomNumberGenerator::seeded(9));
+        let b = Map::new_map_rooms_and_corridors(60, 40, &mut RandomNumberGenerator::seeded(9));
+        assert!(a.tiles == b.tiles);
+        assert!(a.lit_tiles == b.lit_tiles);
+        assert_eq!(a.shop, b.shop);
+    }
+
+    #[test]
+    fn decals_only_go_on_dry_ground_and_faint_ones_dont_cover_others() {
+        let mut map = test_map();
+        map[(1, 1)] = TileType::Floor;
+        map[(2, 1)] = TileType::DeepWater;
+        let (floor, water) = (map.idx(1, 1), map.idx(2, 1));
+
+        map.add_decal(water, Decal::Blood);
+        assert_eq!(map.decals[water], None);
+
+        map.add_decal(floor, Decal::Footprints);
+        assert_eq!(map.decals[floor], Some(Decal::Footprints));
+        map.add_decal(floor, Decal::Blood);
+        map.add_decal(floor, Decal::Footprints);
+        assert_eq!(map.decals[floor], Some(Decal::Blood));
+        map.add_decal(floor, Decal::Scorch);
+        assert_eq!(map.decals[floor], Some(Decal::Scorch));
+    }
 }
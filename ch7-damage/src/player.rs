@@ -1,9 +1,21 @@
-use crate::components::{CanMelee, CombatStats, Player, Position, Viewshed};
-use crate::map::Map;
 use super::{RunState, State};
-use rltk::{Point, Rltk, VirtualKeyCode};
+use crate::components::{
+    CanMelee, CombatStats, Consumable, EntityMoved, Experience, Faction, GoldPile, HungerClock,
+    InBackpack, Item, KnownSpells, Mana, Name, Player, Position, ProvidesFood, Purse, Searching,
+    Swimmer, Vendor, Viewshed, Wading, WantsToCastSpell,
+};
+use crate::factions::{Reaction, Reactions};
+use crate::interrupt::Snapshot;
+use crate::items::{carried_items, display_name, use_item};
+use crate::keymap::{Action, Keymap};
+use crate::map::{Decal, Map, TileType};
+use crate::progression::LevelUpChoice;
+use crate::spells::Spell;
+use crate::systems::awareness_system::VisibleMonsters;
+use crate::trade::{price, trade};
+use crate::travel::start_travel;
+use rltk::{console, DistanceAlg, Point, Rltk, VirtualKeyCode};
 use specs::prelude::*;
-use std::cmp::{max, min};
 
 pub fn try_move_player(dx: i32, dy: i32, ecs: &mut World) {
     /*
@@ -12,13 +24,21 @@ pub fn try_move_player(dx: i32, dy: i32, ecs: &mut World) {
     */
 
     let mut positions = ecs.write_storage::<Position>();
-    let mut players = ecs.write_storage::<Player>();
+    let players = ecs.write_storage::<Player>();
     let mut viewsheds = ecs.write_storage::<Viewshed>();
     let combat_stats = ecs.read_storage::<CombatStats>();
     let mut wants_to_melee = ecs.write_storage::<CanMelee>();
+    let swimmers = ecs.read_storage::<Swimmer>();
+    let mut moved = ecs.write_storage::<EntityMoved>();
+    let mut wading = ecs.write_storage::<Wading>();
+    let factions = ecs.read_storage::<Faction>();
+    let reactions = ecs.fetch::<Reactions>();
     let entities = ecs.entities();
 
-    let map = ecs.fetch::<Map>(); // fetch the Map so we can update it
+    let mut map = ecs.fetch_mut::<Map>(); // fetch the Map so we can update it
+
+    // an ally in the way swaps places with the player: (ally, where it goes)
+    let mut swap: Option<(Entity, Point)> = None;
 
     /*  */
     for (entity, _player, pos, viewshed) in
@@ -30,7 +50,18 @@ pub fn try_move_player(dx: i32, dy: i32, ecs: &mut World) {
         let destination = map.idx(pos.x + dx, pos.y + dy);
 
         for potential_target in map.tile_content[destination].iter() {
-            if let Some(target) = combat_stats.get(*potential_target) {
+            let friendly = match (factions.get(entity), factions.get(*potential_target)) {
+                (Some(a), Some(b)) => reactions.reaction(a.id, b.id) == Reaction::Friendly,
+                _ => false,
+            };
+            // the ally has to be able to stand where the player was
+            let ally_can_swap = !map.tiles[map.idx(pos.x, pos.y)].needs_swimming()
+                || swimmers.get(*potential_target).is_some();
+            if friendly {
+                if ally_can_swap {
+                    swap = Some((*potential_target, Point::new(pos.x, pos.y)));
+                }
+            } else if combat_stats.get(*potential_target).is_some() {
                 wants_to_melee
                     .insert(
                         entity,
@@ -42,49 +73,600 @@ pub fn try_move_player(dx: i32, dy: i32, ecs: &mut World) {
             }
         }
 
-        if !map.blocked[destination] {
+        // Can't swim through deep water unless you're a swimmer
+        let can_enter = !map.tiles[destination].needs_swimming() || swimmers.get(entity).is_some();
+
+        if (!map.blocked[destination] || swap.is_some()) && can_enter {
+            // walking through blood leaves a trail
+            let here = map.idx(pos.x, pos.y);
+            if map.decals[here] == Some(Decal::Blood) {
+                map.add_decal(destination, Decal::Footprints);
+            }
+
             // Can't walk through walls
-            pos.x = min(79, max(0, pos.x + dx));
-            pos.y = min(49, max(0, pos.y + dy));
+            pos.x += dx;
+            pos.y += dy;
+            // monsters move before the map is indexed again, so keep them
+            // from stepping onto the player. A swapped ally takes the old tile.
+            if swap.is_none() {
+                map.blocked[here] = false;
+            }
+            map.blocked[destination] = true;
             viewshed.dirty = true;
+            moved
+                .insert(entity, EntityMoved {})
+                .expect("Unable to insert marker.");
+            if map.tiles[destination] == TileType::ShallowWater {
+                wading
+                    .insert(entity, Wading {})
+                    .expect("Unable to insert marker.");
+            }
 
             // When the player moves, update the position in the ECS
             let mut player_position = ecs.write_resource::<Point>();
             player_position.x = pos.x;
             player_position.y = pos.y;
+        } else {
+            swap = None;
         }
     }
+
+    if let Some((ally, to)) = swap {
+        if let Some(pos) = positions.get_mut(ally) {
+            pos.x = to.x;
+            pos.y = to.y;
+        }
+        if let Some(viewshed) = viewsheds.get_mut(ally) {
+            viewshed.dirty = true;
+        }
+        moved
+            .insert(ally, EntityMoved {})
+            .expect("Unable to insert marker.");
+    }
 }
 
 pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
-    match ctx.key {
+    // clicking on the map travels there
+    if ctx.left_click {
+        return start_travel(gs, ctx);
+    }
+
+    let action = match ctx.key {
         None => return RunState::Paused, // nothing happened
-        Some(key) => match key {
-            // Using standard PC gaming movement controls
-            VirtualKeyCode::W => try_move_player(0, -1, &mut gs.ecs), // up
-            VirtualKeyCode::A => try_move_player(-1, 0, &mut gs.ecs), // left
-            VirtualKeyCode::S => try_move_player(0, 1, &mut gs.ecs),  // down
-            VirtualKeyCode::D => try_move_player(1, 0, &mut gs.ecs),  // right
-
-            // diagonals
-            VirtualKeyCode::Q => try_move_player(-1, -1, &mut gs.ecs),
-            VirtualKeyCode::Z => try_move_player(-1, 1, &mut gs.ecs),
-            VirtualKeyCode::E => try_move_player(1, -1, &mut gs.ecs),
-            VirtualKeyCode::X => try_move_player(1, 1, &mut gs.ecs),
-            // VirtualKeyCode::Numpad4 => try_move_player(-1, 0, &mut gs.ecs),
-            // VirtualKeyCode::H => try_move_player(-1, 0, &mut gs.ecs),
-            // VirtualKeyCode::Right => try_move_player(1, 0, &mut gs.ecs),
-            // VirtualKeyCode::Numpad6 => try_move_player(1, 0, &mut gs.ecs),
-            // VirtualKeyCode::L => try_move_player(1, 0, &mut gs.ecs),
-            // VirtualKeyCode::Up => try_move_player(0, -1, &mut gs.ecs),
-            // VirtualKeyCode::Numpad8 => try_move_player(0, -1, &mut gs.ecs),
-            // VirtualKeyCode::K => try_move_player(0, -1, &mut gs.ecs),
-            // VirtualKeyCode::Down => try_move_player(0, 1, &mut gs.ecs),
-            // VirtualKeyCode::Numpad2 => try_move_player(0, 1, &mut gs.ecs),
-            // VirtualKeyCode::J => try_move_player(0, 1, &mut gs.ecs),
-            _ => return RunState::Paused, // do nothing if any other key is hit
-        },
+        Some(key) => gs.ecs.fetch::<Keymap>().action(key),
+    };
+    let action = match action {
+        None => return RunState::Paused, // do nothing if any other key is hit
+        Some(action) => action,
+    };
+
+    if let Some((dx, dy)) = action.direction() {
+        // holding shift runs until something interesting happens
+        if ctx.shift {
+            return RunState::Run { dx, dy };
+        }
+        // walking into a vendor starts trading
+        if let Some(vendor) = vendor_at(&gs.ecs, dx, dy) {
+            return RunState::Trade {
+                vendor,
+                selling: false,
+            };
+        }
+        try_move_player(dx, dy, &mut gs.ecs);
+        return RunState::Running;
+    }
+
+    match action {
+        // let a turn go by
+        Action::Wait => RunState::Running,
+        Action::Rest => RunState::Rest { turns: 0 },
+        Action::Search => {
+            let player = player_entity(&gs.ecs);
+            gs.ecs
+                .write_storage::<Searching>()
+                .insert(player, Searching {})
+                .expect("Unable to insert search.");
+            RunState::Running
+        }
+        Action::Eat => eat_food(&mut gs.ecs),
+        Action::PickUp => pick_up(&mut gs.ecs),
+        Action::Cast => RunState::SpellMenu,
+        Action::UseItem => RunState::Inventory,
+        // explore until something interesting happens
+        Action::AutoExplore => RunState::AutoExplore,
+        Action::Help => RunState::Help,
+        _ => RunState::Paused,
+    }
+}
+
+/// The player's entity
+pub fn player_entity(ecs: &World) -> Entity {
+    let entities = ecs.entities();
+    let players = ecs.read_storage::<Player>();
+    (&entities, &players)
+        .join()
+        .map(|(entity, _player)| entity)
+        .next()
+        .expect("There is no player.")
+}
+
+/// Eats food lying where the player is standing, or that they're carrying,
+/// which takes a turn
+fn eat_food(ecs: &mut World) -> RunState {
+    let player = player_entity(ecs);
+    let food = {
+        let map = ecs.fetch::<Map>();
+        let pos = *ecs.fetch::<Point>();
+        let provides_food = ecs.read_storage::<ProvidesFood>();
+        let on_floor = map.tile_content[map.point_idx(pos)].iter().copied();
+        // food on the floor is eaten before anything carried
+        let carried = carried_items(ecs, player)
+            .into_iter()
+            .map(|(item, _name)| item);
+        on_floor
+            .chain(carried)
+            .find(|entity| provides_food.get(*entity).is_some())
+    };
+    let food = match food {
+        None => {
+            console::log("You have nothing to eat.");
+            return RunState::Paused;
+        }
+        Some(food) => food,
+    };
+
+    if let Some(name) = ecs.read_storage::<Name>().get(food) {
+        console::log(format!("You eat the {}.", name.name));
+    }
+    ecs.write_storage::<HungerClock>()
+        .insert(player, HungerClock::well_fed())
+        .expect("Unable to insert hunger clock.");
+    ecs.delete_entity(food).expect("Unable to delete food.");
+    RunState::Running
+}
+
+/// Picks up everything lying where the player is standing, which takes a
+/// turn. Gold goes straight into the player's purse.
+fn pick_up(ecs: &mut World) -> RunState {
+    let player = player_entity(ecs);
+    let items: Vec<Entity> = {
+        let map = ecs.fetch::<Map>();
+        let pos = *ecs.fetch::<Point>();
+        let items = ecs.read_storage::<Item>();
+        map.tile_content[map.point_idx(pos)]
+            .iter()
+            .copied()
+            .filter(|entity| items.get(*entity).is_some())
+            .collect()
+    };
+    if items.is_empty() {
+        console::log("There is nothing here to pick up.");
+        return RunState::Paused;
     }
 
+    for item in items {
+        let gold = ecs
+            .read_storage::<GoldPile>()
+            .get(item)
+            .map(|pile| pile.amount);
+        if let Some(gold) = gold {
+            console::log(format!("You pick up {} gold.", gold));
+            if let Some(purse) = ecs.write_storage::<Purse>().get_mut(player) {
+                purse.gold += gold;
+            }
+            ecs.delete_entity(item).expect("Unable to delete gold.");
+            continue;
+        }
+
+        console::log(format!("You pick up the {}.", display_name(ecs, item)));
+        ecs.write_storage::<Position>().remove(item);
+        ecs.write_storage::<InBackpack>()
+            .insert(item, InBackpack { owner: player })
+            .expect("Unable to pick up item.");
+    }
     RunState::Running
 }
+
+/// The vendor the player would walk into by moving (dx, dy), if there is one
+fn vendor_at(ecs: &World, dx: i32, dy: i32) -> Option<Entity> {
+    let map = ecs.fetch::<Map>();
+    let pos = *ecs.fetch::<Point>();
+    let vendors = ecs.read_storage::<Vendor>();
+    if !map.contains(pos.x + dx, pos.y + dy) {
+        return None;
+    }
+    map.tile_content[map.idx(pos.x + dx, pos.y + dy)]
+        .iter()
+        .copied()
+        .find(|entity| vendors.get(*entity).is_some())
+}
+
+/// Waits for the player to buy or sell something. Tab switches between
+/// buying and selling.
+pub fn trade_input(gs: &mut State, ctx: &mut Rltk, vendor: Entity, selling: bool) -> RunState {
+    let key = match ctx.key {
+        None => return RunState::Trade { vendor, selling },
+        Some(VirtualKeyCode::Escape) => return RunState::Paused,
+        Some(VirtualKeyCode::Tab) => {
+            return RunState::Trade {
+                vendor,
+                selling: !selling,
+            }
+        }
+        Some(key) => key,
+    };
+
+    let player = player_entity(&gs.ecs);
+    let (buyer, seller) = if selling {
+        (vendor, player)
+    } else {
+        (player, vendor)
+    };
+    let wares = carried_items(&gs.ecs, seller);
+    if let Some((item, name)) = menu_choice(key).and_then(|i| wares.get(i)) {
+        let price = price(&gs.ecs, vendor, *item, selling);
+        match trade(&mut gs.ecs, buyer, seller, *item, price) {
+            Ok(()) if selling => console::log(format!("You sell the {} for {} gold.", name, price)),
+            Ok(()) => console::log(format!("You buy the {} for {} gold.", name, price)),
+            Err(e) if selling => console::log(format!("The vendor {}.", e)),
+            Err(e) => console::log(format!("You {}.", e)),
+        }
+    }
+    RunState::Trade { vendor, selling }
+}
+
+/// Waits a turn at a time, letting the player regenerate, until they are
+/// fully healed or something interrupts them
+pub fn rest_turn(gs: &mut State, turns: i32) -> RunState {
+    let player = player_entity(&gs.ecs);
+    if !gs.ecs.fetch::<VisibleMonsters>().seen.is_empty() {
+        console::log("You can't rest with monsters in view.");
+        return RunState::Paused;
+    }
+    {
+        let stats = gs.ecs.read_storage::<CombatStats>();
+        let stats = stats.get(player).expect("The player has no stats.");
+        if stats.hp >= stats.max_hp {
+            if turns > 0 {
+                console::log("You feel rested.");
+            } else {
+                console::log("You don't need to rest.");
+            }
+            return RunState::Paused;
+        }
+    }
+    // hungry players don't heal, so resting would never end
+    if let Some(clock) = gs.ecs.read_storage::<HungerClock>().get(player) {
+        if clock.state.regen_per_turn() == 0 {
+            console::log("You are too hungry to rest.");
+            return RunState::Paused;
+        }
+    }
+
+    let before = Snapshot::take(&gs.ecs);
+    gs.run_systems();
+    if let Some(reason) = before.interruption(&gs.ecs) {
+        console::log(format!("You stop resting: {}.", reason));
+        return RunState::Paused;
+    }
+
+    RunState::Rest { turns: turns + 1 }
+}
+
+/// True if the player has run out of hp
+pub fn player_is_dead(ecs: &World) -> bool {
+    let players = ecs.read_storage::<Player>();
+    let combat_stats = ecs.read_storage::<CombatStats>();
+    (&players, &combat_stats)
+        .join()
+        .any(|(_player, stats)| stats.hp < 1)
+}
+
+/// True if the player waded into shallow water and loses a turn to it.
+/// The turn is only lost once.
+pub fn player_loses_turn(ecs: &mut World) -> bool {
+    let players = ecs.read_storage::<Player>();
+    let mut wading = ecs.write_storage::<Wading>();
+    let entities = ecs.entities();
+    let waders: Vec<Entity> = (&entities, &players, &wading)
+        .join()
+        .map(|(entity, _player, _wading)| entity)
+        .collect();
+    for entity in waders.iter() {
+        wading.remove(*entity);
+    }
+    !waders.is_empty()
+}
+
+/// True if the player has enough experience to gain a level
+pub fn player_can_level_up(ecs: &World) -> bool {
+    let players = ecs.read_storage::<Player>();
+    let experience = ecs.read_storage::<Experience>();
+    (&players, &experience)
+        .join()
+        .any(|(_player, experience)| experience.can_level_up())
+}
+
+/// Waits for the player to pick a stat to raise, from the level up screen
+pub fn level_up_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
+    let choice = match ctx.key.and_then(menu_choice) {
+        Some(i) if i < LevelUpChoice::ALL.len() => LevelUpChoice::ALL[i],
+        _ => return RunState::LevelUp,
+    };
+
+    let player = player_entity(&gs.ecs);
+    let mut experience = gs.ecs.write_storage::<Experience>();
+    let mut stats = gs.ecs.write_storage::<CombatStats>();
+    if let (Some(experience), Some(stats)) = (experience.get_mut(player), stats.get_mut(player)) {
+        choice.apply(experience, stats);
+        console::log(format!("Welcome to level {}!", experience.level));
+    }
+    RunState::Paused
+}
+
+/// The potions and scrolls the player is carrying, with display names
+pub fn usable_items(ecs: &World) -> Vec<(Entity, String)> {
+    let consumables = ecs.read_storage::<Consumable>();
+    carried_items(ecs, player_entity(ecs))
+        .into_iter()
+        .filter(|(item, _name)| consumables.get(*item).is_some())
+        .collect()
+}
+
+/// Waits for the player to pick an item to use, which takes a turn
+pub fn inventory_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
+    let key = match ctx.key {
+        None => return RunState::Inventory,
+        Some(VirtualKeyCode::Escape) => return RunState::Paused,
+        Some(key) => key,
+    };
+    let items = usable_items(&gs.ecs);
+    match menu_choice(key).and_then(|i| items.get(i)) {
+        None => RunState::Inventory,
+        Some((item, _name)) => {
+            let player = player_entity(&gs.ecs);
+            use_item(&mut gs.ecs, player, *item);
+            RunState::Running
+        }
+    }
+}
+
+/// Which entry of a numbered menu a key picks, counting from 0
+fn menu_choice(key: VirtualKeyCode) -> Option<usize> {
+    use VirtualKeyCode::*;
+    [Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9]
+        .iter()
+        .position(|k| *k == key)
+}
+
+/// Waits for the player to pick a spell from the spell menu
+pub fn spell_menu_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
+    let key = match ctx.key {
+        None => return RunState::SpellMenu,
+        Some(VirtualKeyCode::Escape) => return RunState::Paused,
+        Some(key) => key,
+    };
+    let player = player_entity(&gs.ecs);
+    let spell = {
+        let known = gs.ecs.read_storage::<KnownSpells>();
+        let spells = known.get(player).map_or(&[][..], |k| &k.spells[..]);
+        match menu_choice(key).and_then(|i| spells.get(i)) {
+            None => return RunState::SpellMenu,
+            Some(spell) => *spell,
+        }
+    };
+
+    let mana = gs
+        .ecs
+        .read_storage::<Mana>()
+        .get(player)
+        .map_or(0, |mana| mana.current);
+    if mana < spell.mana_cost() {
+        console::log("You don't have enough mana.");
+        return RunState::Paused;
+    }
+
+    if spell.range().is_some() {
+        RunState::Targeting {
+            spell,
+            cursor: None,
+        }
+    } else {
+        cast_spell(&mut gs.ecs, spell, None)
+    }
+}
+
+/// Waits for the player to pick a target for the spell, or cancel it. A
+/// target can be clicked on, or Tab steps through the creatures in range and
+/// Enter casts at the one picked.
+pub fn targeting_input(
+    gs: &mut State,
+    ctx: &mut Rltk,
+    spell: Spell,
+    cursor: Option<Point>,
+) -> RunState {
+    match (ctx.key, cursor) {
+        (Some(VirtualKeyCode::Escape), _) => return RunState::Paused,
+        (Some(VirtualKeyCode::Tab), _) => {
+            let cursor = next_creature_target(&gs.ecs, spell, cursor);
+            return RunState::Targeting { spell, cursor };
+        }
+        (Some(VirtualKeyCode::Return), Some(target)) => {
+            return cast_spell(&mut gs.ecs, spell, Some(target));
+        }
+        _ => {}
+    }
+    if !ctx.left_click {
+        return RunState::Targeting { spell, cursor };
+    }
+
+    let (mx, my) = ctx.mouse_pos();
+    let (x, y) = gs.camera().screen_to_world(mx, my);
+    let target = Point::new(x, y);
+    if !spell_targets(&gs.ecs, spell).contains(&target) {
+        console::log("You can't target that.");
+        return RunState::Targeting { spell, cursor };
+    }
+    cast_spell(&mut gs.ecs, spell, Some(target))
+}
+
+/// The creature in range of the spell that comes after `cursor`, nearest
+/// first, going back to the nearest after the furthest
+fn next_creature_target(ecs: &World, spell: Spell, cursor: Option<Point>) -> Option<Point> {
+    let player_position = *ecs.fetch::<Point>();
+    let mut creatures: Vec<Point> = {
+        let map = ecs.fetch::<Map>();
+        let combat_stats = ecs.read_storage::<CombatStats>();
+        spell_targets(ecs, spell)
+            .into_iter()
+            .filter(|p| {
+                map.tile_content[map.point_idx(*p)]
+                    .iter()
+                    .any(|entity| combat_stats.get(*entity).is_some())
+            })
+            .collect()
+    };
+    if creatures.is_empty() {
+        return None;
+    }
+    creatures.sort_by_key(|p| {
+        let (dx, dy) = (p.x - player_position.x, p.y - player_position.y);
+        (dx * dx + dy * dy, p.y, p.x)
+    });
+
+    let next = cursor
+        .and_then(|cursor| creatures.iter().position(|p| *p == cursor))
+        .map_or(0, |i| (i + 1) % creatures.len());
+    Some(creatures[next])
+}
+
+/// The tiles the player could target with the spell: ones they can see,
+/// within its range, other than their own
+pub fn spell_targets(ecs: &World, spell: Spell) -> Vec<Point> {
+    let range = match spell.range() {
+        None => return Vec::new(),
+        Some(range) => range as f32,
+    };
+    let player = player_entity(ecs);
+    let player_position = *ecs.fetch::<Point>();
+    let map = ecs.fetch::<Map>();
+    let viewsheds = ecs.read_storage::<Viewshed>();
+    viewsheds.get(player).map_or(Vec::new(), |viewshed| {
+        viewshed
+            .visible_tiles
+            .iter()
+            .copied()
+            .filter(|p| *p != player_position && map.visible_tiles[map.point_idx(*p)])
+            .filter(|p| DistanceAlg::Pythagoras.distance2d(player_position, *p) <= range)
+            .collect()
+    })
+}
+
+fn cast_spell(ecs: &mut World, spell: Spell, target: Option<Point>) -> RunState {
+    let player = player_entity(ecs);
+    ecs.write_storage::<WantsToCastSpell>()
+        .insert(player, WantsToCastSpell { spell, target })
+        .expect("Unable to insert spell.");
+    RunState::Running
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::BlocksTile;
+    use crate::factions::FactionId;
+    use crate::fov::FovAlgorithm;
+    use crate::map::TileType;
+    use crate::systems::map_indexing_system::MapIndexingSystem;
+
+    /// A world with a row of floor along y = 3, the player at (3, 3) and a
+    /// wolf on their side at (4, 3)
+    fn player_and_ally(player_tile: TileType, player_swims: bool) -> (World, Entity, Entity) {
+        let mut ecs = World::new();
+        ecs.register::<Position>();
+        ecs.register::<Player>();
+        ecs.register::<Viewshed>();
+        ecs.register::<CombatStats>();
+        ecs.register::<CanMelee>();
+        ecs.register::<Swimmer>();
+        ecs.register::<EntityMoved>();
+        ecs.register::<Faction>();
+        ecs.register::<BlocksTile>();
+        ecs.register::<Wading>();
+
+        let mut map = Map::new(8, 8);
+        for x in 1..7 {
+            map[(x, 3)] = TileType::Floor;
+        }
+        map[(3, 3)] = player_tile;
+        ecs.insert(map);
+        ecs.insert(Reactions::default());
+        ecs.insert(Point::new(3, 3));
+
+        let viewshed = || Viewshed {
+            visible_tiles: Vec::new(),
+            range: 8,
+            dirty: false,
+            algorithm: FovAlgorithm::SymmetricShadowcasting,
+        };
+        let mut player = ecs
+            .create_entity()
+            .with(Position::new(3, 3))
+            .with(Player {})
+            .with(viewshed())
+            .with(CombatStats::new(30, 30, 0, 0))
+            .with(Faction {
+                id: FactionId::Player,
+            });
+        if player_swims {
+            player = player.with(Swimmer {});
+        }
+        let player = player.build();
+        let ally = ecs
+            .create_entity()
+            .with(Position::new(4, 3))
+            .with(viewshed())
+            .with(CombatStats::new(10, 10, 0, 0))
+            .with(Faction {
+                id: FactionId::Player,
+            })
+            .with(BlocksTile {})
+            .build();
+        MapIndexingSystem {}.run_now(&ecs);
+        (ecs, player, ally)
+    }
+
+    fn position(ecs: &World, entity: Entity) -> (i32, i32) {
+        let positions = ecs.read_storage::<Position>();
+        let pos = positions.get(entity).unwrap();
+        (pos.x, pos.y)
+    }
+
+    #[test]
+    fn walking_into_an_ally_swaps_places_without_attacking() {
+        let (mut ecs, player, ally) = player_and_ally(TileType::Floor, false);
+        try_move_player(1, 0, &mut ecs);
+        assert_eq!(position(&ecs, player), (4, 3));
+        assert_eq!(position(&ecs, ally), (3, 3));
+        assert!(ecs.read_storage::<CanMelee>().get(player).is_none());
+    }
+
+    #[test]
+    fn allies_that_cant_swim_arent_swapped_into_deep_water() {
+        let (mut ecs, player, ally) = player_and_ally(TileType::DeepWater, true);
+        try_move_player(1, 0, &mut ecs);
+        assert_eq!(position(&ecs, player), (3, 3));
+        assert_eq!(position(&ecs, ally), (4, 3));
+        assert!(ecs.read_storage::<CanMelee>().get(player).is_none());
+    }
+
+    #[test]
+    fn wading_into_shallow_water_costs_a_turn() {
+        let (mut ecs, player, _ally) = player_and_ally(TileType::Floor, false);
+        ecs.write_resource::<Map>()[(2, 3)] = TileType::ShallowWater;
+        try_move_player(-1, 0, &mut ecs);
+        assert_eq!(position(&ecs, player), (2, 3));
+        assert!(player_loses_turn(&mut ecs));
+        assert!(!player_loses_turn(&mut ecs));
+    }
+}
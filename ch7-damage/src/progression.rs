@@ -0,0 +1,90 @@
+// Experience levels, and what the player can improve when they gain one
+
+use crate::components::{CombatStats, Experience};
+
+/// The total experience needed to reach `level`. Each level costs 100 more
+/// than the one before: 100 for level 2, 300 for level 3, 600 for level 4...
+pub fn xp_for_level(level: i32) -> i32 {
+    50 * level * (level - 1)
+}
+
+impl Experience {
+    /// The total experience needed for the next level
+    pub fn next_level_xp(&self) -> i32 {
+        xp_for_level(self.level + 1)
+    }
+
+    /// True if there's enough experience for another level
+    pub fn can_level_up(&self) -> bool {
+        self.xp >= self.next_level_xp()
+    }
+}
+
+/// The stats the player can choose to raise when they level up
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum LevelUpChoice {
+    MaxHp,
+    Power,
+    Defense,
+}
+
+impl LevelUpChoice {
+    /// Every choice, in the order the level up screen lists them
+    pub const ALL: [LevelUpChoice; 3] = [
+        LevelUpChoice::MaxHp,
+        LevelUpChoice::Power,
+        LevelUpChoice::Defense,
+    ];
+
+    /// What the choice does, for the level up screen
+    pub fn description(self) -> &'static str {
+        match self {
+            LevelUpChoice::MaxHp => "Toughness (+10 max hp)",
+            LevelUpChoice::Power => "Strength (+1 power)",
+            LevelUpChoice::Defense => "Agility (+1 defense)",
+        }
+    }
+
+    /// Raises the stat, and the level
+    pub fn apply(self, experience: &mut Experience, stats: &mut CombatStats) {
+        match self {
+            LevelUpChoice::MaxHp => {
+                stats.max_hp += 10;
+                stats.hp += 10;
+            }
+            LevelUpChoice::Power => stats.power += 1,
+            LevelUpChoice::Defense => stats.defense += 1,
+        }
+        experience.level += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_get_more_expensive() {
+        assert_eq!(xp_for_level(1), 0);
+        assert_eq!(xp_for_level(2), 100);
+        assert_eq!(xp_for_level(3), 300);
+        for level in 2..20 {
+            let cost = xp_for_level(level + 1) - xp_for_level(level);
+            assert_eq!(cost, xp_for_level(level) - xp_for_level(level - 1) + 100);
+        }
+    }
+
+    #[test]
+    fn levelling_up_raises_the_level_once() {
+        let mut experience = Experience { level: 1, xp: 350 }; // enough for two levels
+        let mut stats = CombatStats::new(30, 20, 2, 5);
+
+        assert!(experience.can_level_up());
+        LevelUpChoice::MaxHp.apply(&mut experience, &mut stats);
+        assert_eq!((experience.level, stats.max_hp, stats.hp), (2, 40, 30));
+        assert!(experience.can_level_up());
+        LevelUpChoice::Power.apply(&mut experience, &mut stats);
+        assert_eq!((experience.level, stats.power), (3, 6));
+        assert!(!experience.can_level_up());
+    }
+}
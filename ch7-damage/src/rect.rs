@@ -1,4 +1,5 @@
 /// A Rectangle with bottom edge (x1, x2) and left edge (y1, y2)
+#[derive(Clone)]
 pub struct Rect {
     pub x1: i32,
     pub x2: i32,
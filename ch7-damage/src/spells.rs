@@ -0,0 +1,44 @@
+// The spells that can be cast, and what each one costs
+
+/// Every spell there is
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Spell {
+    MagicMissile,
+    Heal,
+    Blink,
+    Confuse,
+    CallWolf,
+}
+
+impl Spell {
+    pub fn name(self) -> &'static str {
+        match self {
+            Spell::MagicMissile => "Magic Missile",
+            Spell::Heal => "Heal",
+            Spell::Blink => "Blink",
+            Spell::Confuse => "Confuse",
+            Spell::CallWolf => "Call Wolf",
+        }
+    }
+
+    /// How much mana it takes to cast
+    pub fn mana_cost(self) -> i32 {
+        match self {
+            Spell::MagicMissile => 3,
+            Spell::Heal => 4,
+            Spell::Blink => 5,
+            Spell::Confuse => 4,
+            Spell::CallWolf => 8,
+        }
+    }
+
+    /// How far away the target can be, for spells that need one. Spells
+    /// without a range affect the caster.
+    pub fn range(self) -> Option<i32> {
+        match self {
+            Spell::MagicMissile => Some(6),
+            Spell::Confuse => Some(6),
+            Spell::Heal | Spell::Blink | Spell::CallWolf => None,
+        }
+    }
+}
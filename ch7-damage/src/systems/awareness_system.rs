@@ -0,0 +1,90 @@
+// Keeps track of which monsters the player can see, and which of those can
+// see the player
+
+use crate::components::{Faction, Monster, Name, Position, Viewshed};
+use crate::factions::{FactionId, Reaction, Reactions};
+use crate::map::Map;
+use rltk::{console, Point};
+use specs::prelude::*;
+
+/// A monster that the player can see this turn
+pub struct SeenMonster {
+    pub entity: Entity,
+    // true if the monster can see the player too
+    pub sees_player: bool,
+}
+
+/// Resource listing the monsters the player can see this turn
+#[derive(Default)]
+pub struct VisibleMonsters {
+    pub seen: Vec<SeenMonster>,
+    // monsters that weren't visible last turn. Anything that takes more than
+    // one turn, like resting or running, should stop when this isn't empty.
+    pub newly_seen: Vec<Entity>,
+}
+
+pub struct AwarenessSystem {}
+
+impl<'a> System<'a> for AwarenessSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        ReadExpect<'a, Map>,
+        ReadExpect<'a, Point>,
+        WriteExpect<'a, VisibleMonsters>,
+        Entities<'a>,
+        ReadStorage<'a, Monster>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Viewshed>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, Faction>,
+        ReadExpect<'a, Reactions>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            map,
+            player_position,
+            mut visible,
+            entities,
+            monsters,
+            positions,
+            viewsheds,
+            names,
+            factions,
+            reactions,
+        ) = data;
+
+        let previously_seen: Vec<Entity> = visible.seen.iter().map(|s| s.entity).collect();
+        visible.seen.clear();
+        visible.newly_seen.clear();
+
+        let player_lit = map.is_lit(map.point_idx(*player_position));
+        for (entity, _monster, pos, viewshed) in
+            (&entities, &monsters, &positions, &viewsheds).join()
+        {
+            if !map.visible_tiles[map.idx(pos.x, pos.y)] {
+                continue;
+            }
+            // the player's allies are no cause for alarm
+            let friendly = factions.get(entity).is_some_and(|faction| {
+                reactions.reaction(faction.id, FactionId::Player) == Reaction::Friendly
+            });
+            if friendly {
+                continue;
+            }
+
+            if !previously_seen.contains(&entity) {
+                if let Some(name) = names.get(entity) {
+                    console::log(format!("You see {}.", name.name));
+                }
+                visible.newly_seen.push(entity);
+            }
+
+            let sees_player = player_lit && viewshed.visible_tiles.contains(&*player_position);
+            visible.seen.push(SeenMonster {
+                entity,
+                sees_player,
+            });
+        }
+    }
+}
@@ -1,39 +1,316 @@
-use crate::components::{CombatStats, SuffersDamage};
+use crate::components::{
+    CombatStats, DamageType, Experience, GoldPile, InBackpack, Item, LastAttacker, Name, Player,
+    Position, Purse, RenderOrder, Renderable, Resistances, SuffersDamage, XpReward,
+};
+use crate::map::{Decal, Map};
+use crate::systems::particle_system::ParticleBuilder;
+use rltk::{console, Point, RGB};
 use specs::prelude::*;
 
+impl Resistances {
+    /// How much of `amount` damage of the given kind actually gets through
+    pub fn adjust(&self, kind: DamageType, amount: i32) -> i32 {
+        if self.immune_to.contains(&kind) {
+            0
+        } else if self.resists.contains(&kind) {
+            amount / 2
+        } else if self.vulnerable_to.contains(&kind) {
+            amount * 2
+        } else {
+            amount
+        }
+    }
+}
+
 pub struct DamageSystem {}
 
 impl<'a> System<'a> for DamageSystem {
+    #[allow(clippy::type_complexity)]
     type SystemData = (
+        WriteExpect<'a, Map>,
+        Entities<'a>,
+        ReadStorage<'a, Position>,
         WriteStorage<'a, CombatStats>,
         WriteStorage<'a, SuffersDamage>,
+        ReadStorage<'a, Resistances>,
+        WriteStorage<'a, LastAttacker>,
+        WriteStorage<'a, Experience>,
+        ReadStorage<'a, XpReward>,
+        ReadStorage<'a, Player>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut stats, mut damage) = data;
+        let (
+            mut map,
+            entities,
+            positions,
+            mut stats,
+            mut damage,
+            resistances,
+            mut last_attackers,
+            mut experience,
+            rewards,
+            players,
+        ) = data;
+
+        // (killer, victim) for every killing blow this turn
+        let mut kills: Vec<(Entity, Entity)> = Vec::new();
+        for (victim, stats, damage) in (&entities, &mut stats, &damage).join() {
+            for hit in damage.damage.iter() {
+                let amount = match resistances.get(victim) {
+                    Some(resistances) => resistances.adjust(hit.kind, hit.amount),
+                    None => hit.amount,
+                };
+                // fire, hunger and poison aren't anyone's fault, so whoever
+                // hit it before isn't to blame for what happens next
+                match hit.source {
+                    Some(source) => {
+                        last_attackers
+                            .insert(victim, LastAttacker { entity: source })
+                            .expect("Unable to insert attacker.");
+                    }
+                    None => {
+                        last_attackers.remove(victim);
+                    }
+                }
 
-        for (mut stats, damage) in (&mut stats, &damage).join() {
-            stats.hp -= damage.amount.iter().sum::<i32>();
+                // anything that gets hurt leaves a mark on the floor
+                let decal = match hit.kind {
+                    DamageType::Slashing | DamageType::Piercing => Some(Decal::Blood),
+                    DamageType::Fire => Some(Decal::Scorch),
+                    DamageType::Poison | DamageType::Magic | DamageType::Starvation => None,
+                };
+                if let (Some(decal), Some(pos)) = (decal, positions.get(victim)) {
+                    if amount > 0 {
+                        let idx = map.idx(pos.x, pos.y);
+                        map.add_decal(idx, decal);
+                    }
+                }
+
+                let was_alive = stats.hp > 0;
+                stats.hp -= amount;
+                if was_alive && stats.hp < 1 {
+                    if let Some(killer) = hit.source {
+                        kills.push((killer, victim));
+                    }
+                }
+            }
         }
 
         damage.clear();
+
+        for (killer, victim) in kills {
+            let (experience, reward) = match (experience.get_mut(killer), rewards.get(victim)) {
+                (Some(experience), Some(reward)) => (experience, reward),
+                _ => continue,
+            };
+            experience.xp += reward.xp;
+            if players.get(killer).is_some() {
+                console::log(format!("You gain {} xp.", reward.xp));
+            }
+        }
     }
 }
 
+/// Removes everything that died this turn, leaving a corpse and whatever it
+/// was carrying behind. The player isn't removed; the game is over instead.
 pub fn delete_the_dead(ecs: &mut World) {
-    let mut dead: Vec<Entity> = Vec::new();
+    // (victim, where it died, its name)
+    let mut dead: Vec<(Entity, Option<Point>, String)> = Vec::new();
     {
         let combat_stats = ecs.read_storage::<CombatStats>();
+        let names = ecs.read_storage::<Name>();
+        let positions = ecs.read_storage::<Position>();
+        let players = ecs.read_storage::<Player>();
+        let last_attackers = ecs.read_storage::<LastAttacker>();
         let entities = ecs.entities();
-    
-        for (entity, stats) in (&entities, &combat_stats).join() {
+
+        for (entity, stats, _player) in (&entities, &combat_stats, !&players).join() {
             if stats.hp < 1 {
-                dead.push(entity);
+                let victim = names
+                    .get(entity)
+                    .map_or("Something".to_string(), |n| n.name.clone());
+                match killer_name(&last_attackers, &names, entity) {
+                    Some(killer) => console::log(format!("{} is killed by {}.", victim, killer)),
+                    None => console::log(format!("{} dies.", victim)),
+                }
+                let pos = positions.get(entity).map(|pos| Point::new(pos.x, pos.y));
+                dead.push((entity, pos, victim));
             }
         }
     }
 
-    for victim in dead {
+    for (victim, pos, name) in dead {
+        if let Some(pos) = pos {
+            ecs.fetch_mut::<ParticleBuilder>().request(
+                pos.x,
+                pos.y,
+                RGB::named(rltk::RED),
+                rltk::to_cp437('☼'),
+                400.0,
+            );
+            drop_inventory(ecs, victim, pos);
+            ecs.create_entity()
+                .with(Position::new(pos.x, pos.y))
+                .with(Renderable {
+                    glyph: rltk::to_cp437('%'),
+                    fg: RGB::named(rltk::DARK_RED),
+                    bg: RGB::named(rltk::BLACK),
+                    render_order: RenderOrder::Item,
+                })
+                .with(Name {
+                    name: format!("{} corpse", name),
+                })
+                .build();
+        }
         ecs.delete_entity(victim).expect("Unable to delete.");
     }
 }
+
+/// The name of whatever last hurt `victim`, if it had one
+pub fn killer_name(
+    last_attackers: &ReadStorage<LastAttacker>,
+    names: &ReadStorage<Name>,
+    victim: Entity,
+) -> Option<String> {
+    last_attackers
+        .get(victim)
+        .and_then(|attacker| names.get(attacker.entity))
+        .map(|name| name.name.clone())
+}
+
+/// Puts everything `owner` is carrying on the floor at `pos`, including
+/// their gold
+fn drop_inventory(ecs: &mut World, owner: Entity, pos: Point) {
+    let gold = ecs
+        .read_storage::<Purse>()
+        .get(owner)
+        .map_or(0, |purse| purse.gold);
+    if gold > 0 {
+        ecs.create_entity()
+            .with(Position::new(pos.x, pos.y))
+            .with(Renderable {
+                glyph: rltk::to_cp437('$'),
+                fg: RGB::named(rltk::GOLD),
+                bg: RGB::named(rltk::BLACK),
+                render_order: RenderOrder::Item,
+            })
+            .with(Name {
+                name: format!("{} gold", gold),
+            })
+            .with(Item {})
+            .with(GoldPile { amount: gold })
+            .build();
+    }
+
+    let entities = ecs.entities();
+    let mut backpacks = ecs.write_storage::<InBackpack>();
+    let mut positions = ecs.write_storage::<Position>();
+
+    let carried: Vec<Entity> = (&entities, &backpacks)
+        .join()
+        .filter(|(_item, backpack)| backpack.owner == owner)
+        .map(|(item, _backpack)| item)
+        .collect();
+    for item in carried {
+        backpacks.remove(item);
+        positions
+            .insert(item, Position::new(pos.x, pos.y))
+            .expect("Unable to drop item.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resistances_change_damage() {
+        let resistances = Resistances {
+            resists: vec![DamageType::Slashing],
+            vulnerable_to: vec![DamageType::Fire],
+            immune_to: vec![DamageType::Poison],
+        };
+        assert_eq!(resistances.adjust(DamageType::Slashing, 5), 2);
+        assert_eq!(resistances.adjust(DamageType::Fire, 5), 10);
+        assert_eq!(resistances.adjust(DamageType::Poison, 5), 0);
+        assert_eq!(resistances.adjust(DamageType::Piercing, 5), 5);
+    }
+
+    /// A world with a map and two entities that can earn experience
+    fn world_with_attackers() -> (World, Entity, Entity) {
+        let mut ecs = World::new();
+        ecs.register::<Position>();
+        ecs.register::<CombatStats>();
+        ecs.register::<SuffersDamage>();
+        ecs.register::<Resistances>();
+        ecs.register::<LastAttacker>();
+        ecs.register::<Experience>();
+        ecs.register::<XpReward>();
+        ecs.register::<Player>();
+        ecs.insert(Map::new(10, 10));
+
+        let a = ecs
+            .create_entity()
+            .with(Experience { level: 1, xp: 0 })
+            .build();
+        let b = ecs
+            .create_entity()
+            .with(Experience { level: 1, xp: 0 })
+            .build();
+        (ecs, a, b)
+    }
+
+    fn hurt(ecs: &mut World, victim: Entity, amount: i32, source: Option<Entity>) {
+        let kind = if source.is_some() {
+            DamageType::Slashing
+        } else {
+            DamageType::Fire
+        };
+        SuffersDamage::new_damage(
+            &mut ecs.write_storage::<SuffersDamage>(),
+            victim,
+            amount,
+            kind,
+            source,
+        );
+    }
+
+    fn xp(ecs: &World, entity: Entity) -> i32 {
+        ecs.read_storage::<Experience>().get(entity).unwrap().xp
+    }
+
+    #[test]
+    fn the_finishing_blow_gets_the_credit() {
+        let (mut ecs, a, b) = world_with_attackers();
+        let victim = ecs
+            .create_entity()
+            .with(CombatStats::new(5, 5, 0, 0))
+            .with(XpReward { xp: 10 })
+            .build();
+        hurt(&mut ecs, victim, 5, Some(a));
+        hurt(&mut ecs, victim, 3, Some(b));
+        DamageSystem {}.run_now(&ecs);
+
+        assert_eq!(xp(&ecs, a), 10);
+        assert_eq!(xp(&ecs, b), 0);
+    }
+
+    #[test]
+    fn unsourced_damage_forgets_the_last_attacker() {
+        let (mut ecs, a, _b) = world_with_attackers();
+        let victim = ecs
+            .create_entity()
+            .with(CombatStats::new(5, 5, 0, 0))
+            .with(XpReward { xp: 10 })
+            .build();
+        hurt(&mut ecs, victim, 2, Some(a));
+        DamageSystem {}.run_now(&ecs);
+        assert!(ecs.read_storage::<LastAttacker>().get(victim).is_some());
+
+        hurt(&mut ecs, victim, 5, None);
+        DamageSystem {}.run_now(&ecs);
+        assert!(ecs.read_storage::<LastAttacker>().get(victim).is_none());
+        assert_eq!(xp(&ecs, a), 0);
+    }
+}
@@ -0,0 +1,39 @@
+// Damages entities that are standing on dangerous terrain, like lava
+
+use crate::components::{CombatStats, DamageType, Name, Position, SuffersDamage};
+use crate::map::Map;
+use rltk::console;
+use specs::prelude::*;
+
+pub struct HazardSystem {}
+
+impl<'a> System<'a> for HazardSystem {
+    type SystemData = (
+        ReadExpect<'a, Map>,
+        Entities<'a>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, CombatStats>,
+        ReadStorage<'a, Name>,
+        WriteStorage<'a, SuffersDamage>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (map, entities, positions, combat_stats, names, mut suffer_damage) = data;
+
+        for (entity, pos, _stats) in (&entities, &positions, &combat_stats).join() {
+            let tile = map[(pos.x, pos.y)];
+            if let Some(damage) = tile.damage_on_enter() {
+                if let Some(name) = names.get(entity) {
+                    console::log(format!("{} is burned for {} hp.", &name.name, damage));
+                }
+                SuffersDamage::new_damage(
+                    &mut suffer_damage,
+                    entity,
+                    damage,
+                    DamageType::Fire,
+                    None,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+// Makes entities that need to eat hungrier every turn, and hurts them when
+// they're starving
+
+use crate::components::{DamageType, HungerClock, HungerState, Player, SuffersDamage};
+use rltk::console;
+use specs::prelude::*;
+
+// how many turns each state lasts before the next one
+const NORMAL_TURNS: i32 = 300;
+const HUNGRY_TURNS: i32 = 200;
+
+// hp lost each turn while starving
+const STARVATION_DAMAGE: i32 = 1;
+
+pub struct HungerSystem {}
+
+impl<'a> System<'a> for HungerSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Player>,
+        WriteStorage<'a, HungerClock>,
+        WriteStorage<'a, SuffersDamage>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, players, mut hunger_clocks, mut suffer_damage) = data;
+
+        for (entity, clock) in (&entities, &mut hunger_clocks).join() {
+            let is_player = players.get(entity).is_some();
+
+            // the player was told when they started starving, so this
+            // just hurts
+            if clock.state == HungerState::Starving {
+                SuffersDamage::new_damage(
+                    &mut suffer_damage,
+                    entity,
+                    STARVATION_DAMAGE,
+                    DamageType::Starvation,
+                    None,
+                );
+                continue;
+            }
+
+            clock.duration -= 1;
+            if clock.duration > 0 {
+                continue;
+            }
+
+            let message = match clock.state {
+                HungerState::WellFed => {
+                    clock.state = HungerState::Normal;
+                    clock.duration = NORMAL_TURNS;
+                    "You are no longer well fed."
+                }
+                HungerState::Normal => {
+                    clock.state = HungerState::Hungry;
+                    clock.duration = HUNGRY_TURNS;
+                    "You are hungry."
+                }
+                HungerState::Hungry => {
+                    clock.state = HungerState::Starving;
+                    "You are starving!"
+                }
+                HungerState::Starving => continue,
+            };
+            if is_player {
+                console::log(message);
+            }
+        }
+    }
+}
@@ -0,0 +1,64 @@
+// Works out how much light falls on each tile of the map
+
+use crate::components::{LightSource, Position};
+use crate::map::Map;
+use rltk::{field_of_view, DistanceAlg, Point, RGB};
+use specs::prelude::*;
+
+// light in lit rooms is plain white
+const AMBIENT_LIGHT: (f32, f32, f32) = (1.0, 1.0, 1.0);
+// how bright a light source is at the edge of its radius
+const MIN_FALLOFF: f32 = 0.25;
+
+pub struct LightingSystem {}
+
+impl<'a> System<'a> for LightingSystem {
+    type SystemData = (
+        WriteExpect<'a, Map>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, LightSource>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut map, positions, lights) = data;
+
+        // start with the ambient light
+        let (r, g, b) = AMBIENT_LIGHT;
+        let mut light: Vec<RGB> = map
+            .lit_tiles
+            .iter()
+            .map(|lit| {
+                if *lit {
+                    RGB::from_f32(r, g, b)
+                } else {
+                    RGB::from_f32(0., 0., 0.)
+                }
+            })
+            .collect();
+
+        // then add the light given off by each light source, which gets
+        // dimmer further from the source
+        for (pos, light_source) in (&positions, &lights).join() {
+            let source = Point::new(pos.x, pos.y);
+            let mut lit = field_of_view(source, light_source.radius, &*map);
+            lit.retain(|p| map.contains_point(*p));
+
+            for p in lit.iter() {
+                let distance = DistanceAlg::Pythagoras.distance2d(source, *p);
+                let falloff = 1.0 - (1.0 - MIN_FALLOFF) * (distance / light_source.radius as f32);
+                let i = map.point_idx(*p);
+                light[i] = brighten(light[i], light_source.colour * falloff.max(MIN_FALLOFF));
+            }
+        }
+
+        // what can be seen only needs updating if the light changed
+        map.light_changed = map.light != light;
+        map.light = light;
+    }
+}
+
+/// Adds two lights together without going past full brightness
+fn brighten(a: RGB, b: RGB) -> RGB {
+    let sum = a + b;
+    RGB::from_f32(sum.r.min(1.0), sum.g.min(1.0), sum.b.min(1.0))
+}
@@ -1,39 +1,92 @@
-use crate::components::{CanMelee, CombatStats, Name, SuffersDamage};
-use rltk::console;
+use crate::combat::{armour_class, roll_attack, AttackOutcome};
+use crate::components::{
+    CanMelee, CombatStats, DamageType, MeleeDamage, Name, Position, SuffersDamage,
+};
+use crate::systems::particle_system::ParticleBuilder;
+use rltk::{console, DiceType, RandomNumberGenerator, RGB};
 use specs::prelude::*;
 
 pub struct MeleeCombatSystem {}
 
 impl<'a> System<'a> for MeleeCombatSystem {
+    #[allow(clippy::type_complexity)]
     type SystemData = (
+        WriteExpect<'a, RandomNumberGenerator>,
+        WriteExpect<'a, ParticleBuilder>,
         Entities<'a>,
         WriteStorage<'a, CanMelee>,
         ReadStorage<'a, Name>,
         ReadStorage<'a, CombatStats>,
+        ReadStorage<'a, MeleeDamage>,
         WriteStorage<'a, SuffersDamage>,
+        ReadStorage<'a, Position>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (ents, mut can_melee, names, combat_stats, mut suffer_damage) = data;
+        let (
+            mut rng,
+            mut particles,
+            ents,
+            mut can_melee,
+            names,
+            combat_stats,
+            melee_damage,
+            mut suffer_damage,
+            positions,
+        ) = data;
 
-        for (_ent, can_melee, name, stats) in (&ents, &can_melee, &names, &combat_stats).join() {
+        for (ent, can_melee, name, stats) in (&ents, &can_melee, &names, &combat_stats).join() {
             if stats.hp > 0 {
                 // do if this entity is not dead
                 let target_stats = combat_stats.get(can_melee.target).unwrap();
                 if target_stats.hp > 0 {
                     // target not dead
-                    let target_name = names.get(can_melee.target).unwrap();
-                    let damage = i32::max(0, stats.power - target_stats.defense);
+                    let target_name = &names.get(can_melee.target).unwrap().name;
+                    // anything without a weapon just flails
+                    let dice = melee_damage
+                        .get(ent)
+                        .map_or(DiceType::default(), |melee| melee.dice);
+                    let outcome = roll_attack(
+                        &mut rng,
+                        stats.power,
+                        armour_class(target_stats.defense),
+                        dice,
+                    );
 
-                    if damage == 0 {
-                        console::log(&format!("{} can't hurt {:?}.", &name.name, &target_name));
-                    } else {
-                        console::log(&format!(
-                            "{} hit {:?}, for {} hp.",
-                            &name.name, &target_name, &damage
-                        ));
-                        SuffersDamage::new_damage(&mut suffer_damage, can_melee.target, damage);
+                    let (damage, colour) = match outcome {
+                        AttackOutcome::Fumble => {
+                            console::log(format!("{} fumbles.", &name.name));
+                            continue;
+                        }
+                        AttackOutcome::Miss => {
+                            console::log(format!("{} misses {}.", &name.name, target_name));
+                            continue;
+                        }
+                        AttackOutcome::Hit(damage) => {
+                            console::log(format!(
+                                "{} hits {}, for {} hp.",
+                                &name.name, target_name, damage
+                            ));
+                            (damage, RGB::named(rltk::ORANGE))
+                        }
+                        AttackOutcome::Critical(damage) => {
+                            console::log(format!(
+                                "{} critically hits {}, for {} hp!",
+                                &name.name, target_name, damage
+                            ));
+                            (damage, RGB::named(rltk::RED))
+                        }
+                    };
+                    if let Some(pos) = positions.get(can_melee.target) {
+                        particles.request(pos.x, pos.y, colour, rltk::to_cp437('‼'), 200.0);
                     }
+                    SuffersDamage::new_damage(
+                        &mut suffer_damage,
+                        can_melee.target,
+                        damage,
+                        DamageType::Slashing,
+                        Some(ent),
+                    );
                 }
             }
         }
@@ -0,0 +1,107 @@
+// Remembers where the player last saw things, so they can still be drawn
+// after they go out of view
+
+use crate::components::{
+    Hidden, Monster, ParticleLifetime, Player, Position, RenderOrder, Renderable,
+};
+use crate::map::Map;
+use rltk::RGB;
+use specs::prelude::*;
+use std::collections::HashMap;
+
+// how many turns the player remembers where a monster was
+const MONSTER_MEMORY_TURNS: i32 = 20;
+
+/// Where the player last saw an entity, and what it looked like
+pub struct Ghost {
+    pub x: i32,
+    pub y: i32,
+    pub glyph: u8,
+    pub fg: RGB,
+    pub render_order: RenderOrder,
+    // None for things that are remembered forever, like items
+    pub turns_left: Option<i32>,
+}
+
+/// Resource holding everything the player remembers seeing
+#[derive(Default)]
+pub struct EntityMemory {
+    pub ghosts: HashMap<Entity, Ghost>,
+}
+
+pub struct MemorySystem {}
+
+impl<'a> System<'a> for MemorySystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        ReadExpect<'a, Map>,
+        WriteExpect<'a, EntityMemory>,
+        Entities<'a>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Renderable>,
+        ReadStorage<'a, Hidden>,
+        ReadStorage<'a, Monster>,
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, ParticleLifetime>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            map,
+            mut memory,
+            entities,
+            positions,
+            renderables,
+            hidden,
+            monsters,
+            players,
+            particles,
+        ) = data;
+
+        // monsters fade from memory; things that are gone are forgotten
+        memory.ghosts.retain(|entity, ghost| {
+            if let Some(turns) = ghost.turns_left.as_mut() {
+                *turns -= 1;
+            }
+            entities.is_alive(*entity) && ghost.turns_left.is_none_or(|t| t > 0)
+        });
+
+        // a ghost on a tile that can be seen again is out of date
+        memory
+            .ghosts
+            .retain(|_entity, ghost| !map.visible_tiles[map.idx(ghost.x, ghost.y)]);
+
+        // remember everything that can be seen now, except passing effects
+        for (entity, pos, render, _hidden, _player, _particle) in (
+            &entities,
+            &positions,
+            &renderables,
+            !&hidden,
+            !&players,
+            !&particles,
+        )
+            .join()
+        {
+            if !map.visible_tiles[map.idx(pos.x, pos.y)] {
+                continue;
+            }
+
+            let turns_left = if monsters.get(entity).is_some() {
+                Some(MONSTER_MEMORY_TURNS)
+            } else {
+                None
+            };
+            memory.ghosts.insert(
+                entity,
+                Ghost {
+                    x: pos.x,
+                    y: pos.y,
+                    glyph: render.glyph,
+                    fg: render.fg,
+                    render_order: render.render_order,
+                    turns_left,
+                },
+            );
+        }
+    }
+}
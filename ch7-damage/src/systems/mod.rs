@@ -1,5 +1,15 @@
+pub mod awareness_system;
 pub mod damage_system;
+pub mod hazard_system;
+pub mod hunger_system;
+pub mod lighting_system;
 pub mod map_indexing_system;
 pub mod melee_combat_system;
+pub mod memory_system;
 pub mod monster_ai_system;
+pub mod particle_system;
+pub mod perception_system;
+pub mod regen_system;
+pub mod spell_system;
+pub mod trap_system;
 pub mod visibility_system;
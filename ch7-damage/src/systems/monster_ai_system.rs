@@ -1,8 +1,13 @@
 // Behavior for monsters
 
-use crate::components::{Monster, Name, Position, Viewshed};
-use crate::map::Map;
-use rltk::{console, DistanceAlg, Point};
+use crate::components::{
+    CanMelee, CombatStats, Confusion, EntityMoved, Faction, Follower, HeardNoise, KnownSpells,
+    Mana, Monster, Position, Viewshed, Wading, WantsToCastSpell,
+};
+use crate::factions::{Reaction, Reactions};
+use crate::map::{Map, TileType};
+use crate::spells::Spell;
+use rltk::{DistanceAlg, Point};
 use specs::prelude::*;
 
 pub struct MonsterAI {}
@@ -12,44 +17,358 @@ impl<'a> System<'a> for MonsterAI {
     #[allow(clippy::type_complexity)]
     type SystemData = (
         WriteExpect<'a, Map>,
-        ReadExpect<'a, Point>,
+        ReadExpect<'a, Reactions>,
+        Entities<'a>,
         WriteStorage<'a, Viewshed>,
         ReadStorage<'a, Monster>,
-        ReadStorage<'a, Name>,
+        ReadStorage<'a, Faction>,
         WriteStorage<'a, Position>,
+        WriteStorage<'a, EntityMoved>,
+        WriteStorage<'a, HeardNoise>,
+        WriteStorage<'a, Confusion>,
+        WriteStorage<'a, Wading>,
+        ReadStorage<'a, CombatStats>,
+        ReadStorage<'a, Mana>,
+        ReadStorage<'a, KnownSpells>,
+        WriteStorage<'a, WantsToCastSpell>,
+        WriteStorage<'a, CanMelee>,
+        ReadStorage<'a, Follower>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut map, player_position, mut viewshed, monster, name, mut position) = data;
+        let (
+            mut map,
+            reactions,
+            entities,
+            mut viewshed,
+            monster,
+            factions,
+            mut position,
+            mut moved,
+            mut noises,
+            mut confusion,
+            mut wading,
+            combat_stats,
+            mana,
+            known_spells,
+            mut wants_to_cast,
+            mut wants_to_melee,
+            followers,
+        ) = data;
 
-        for (mut viewshed, _monster, name, mut pos) in
-            (&mut viewshed, &monster, &name, &mut position).join()
+        // everything that could be fought, and where it is. Kept up to date
+        // as monsters move, so later monsters don't chase where they were.
+        let mut creatures: Vec<(Entity, Point, Faction)> =
+            (&entities, &position, &factions, &combat_stats)
+                .join()
+                .map(|(entity, pos, faction, _stats)| (entity, Point::new(pos.x, pos.y), *faction))
+                .collect();
+
+        let mut done_investigating: Vec<Entity> = Vec::new();
+
+        for (entity, viewshed, _monster, faction, pos) in
+            (&entities, &mut viewshed, &monster, &factions, &mut position).join()
         {
-            let p = Point::new(pos.x, pos.y);
-            let distance = DistanceAlg::Pythagoras.distance2d(p, *player_position);
+            // confused monsters lose their turn
+            if let Some(confused) = confusion.get_mut(entity) {
+                confused.turns -= 1;
+                if confused.turns < 1 {
+                    confusion.remove(entity);
+                }
+                continue;
+            }
 
-            if distance < 1.5 {
-                // attack happens here
-                let message = format!("{} prepares for battle.", name.name);
-                console::log(&message);
-                return;
+            // so do ones that just waded into shallow water
+            if wading.remove(entity).is_some() {
+                continue;
             }
 
-            // if true, the player position is visible to the monster
-            if viewshed.visible_tiles.contains(&*player_position) {
-                // A* search for path from monster to player
-                let path = rltk::a_star_search(
-                    map.idx(pos.x, pos.y) as i32,
-                    map.idx(player_position.x, player_position.y) as i32,
-                    &mut *map,
+            // the closest enemy the monster can see, if there is one. Things
+            // standing in the dark can't be seen.
+            let p = Point::new(pos.x, pos.y);
+            let enemy = creatures
+                .iter()
+                .filter(|(other, _, _)| *other != entity)
+                .filter(|(_, _, other)| {
+                    reactions.reaction(faction.id, other.id) == Reaction::Hostile
+                })
+                .filter(|(_, at, _)| {
+                    viewshed.visible_tiles.contains(at) && map.is_lit(map.point_idx(*at))
+                })
+                .map(|(other, at, _)| (*other, *at, DistanceAlg::Pythagoras.distance2d(p, *at)))
+                .min_by(|a, b| a.2.partial_cmp(&b.2).unwrap());
+
+            if let Some((target, at, distance)) = enemy {
+                let spell = choose_spell(
+                    combat_stats.get(entity),
+                    mana.get(entity),
+                    known_spells.get(entity),
+                    distance,
                 );
+                if let Some(spell) = spell {
+                    let target = spell.range().map(|_| at);
+                    wants_to_cast
+                        .insert(entity, WantsToCastSpell { spell, target })
+                        .expect("Unable to insert spell.");
+                    continue;
+                }
+
+                if distance < 1.5 {
+                    wants_to_melee
+                        .insert(entity, CanMelee { target })
+                        .expect("Unable to insert attack.");
+                    continue;
+                }
+            }
+
+            // allies that have strayed too far go back to their leader first
+            let leader = followers.get(entity).and_then(|follower| {
+                creatures
+                    .iter()
+                    .find(|(other, _, _)| *other == follower.leader)
+                    .map(|(_, at, _)| (*at, follower.leash as f32))
+            });
+            let too_far =
+                leader.filter(|(at, leash)| DistanceAlg::Pythagoras.distance2d(p, *at) > *leash);
 
-                if path.success && path.steps.len() > 1 {
-                    pos.x = path.steps[1] as i32 % map.width;
-                    pos.y = path.steps[1] as i32 / map.width;
+            // if an enemy is visible, chase it; otherwise go and look at
+            // anything the monster heard
+            let goal = if let Some((at, _)) = too_far {
+                at
+            } else if let Some((_, at, _)) = enemy {
+                if noises.get(entity).is_some() {
+                    done_investigating.push(entity);
+                }
+                at
+            } else if let Some(noise) = noises.get(entity) {
+                noise.at
+            } else {
+                continue;
+            };
+
+            // A* search for path from monster to the goal. Whatever is
+            // standing on the goal would block the path, so the goal is
+            // treated as open while searching.
+            let here = map.idx(pos.x, pos.y);
+            let goal_idx = map.point_idx(goal);
+            let goal_blocked = map.blocked[goal_idx];
+            map.blocked[goal_idx] = false;
+            let path = rltk::a_star_search(here as i32, goal_idx as i32, &*map);
+            map.blocked[goal_idx] = goal_blocked;
+
+            let next = if path.success && path.steps.len() > 1 {
+                Some(path.steps[1])
+            } else {
+                None
+            };
+            match next {
+                // don't walk into whatever is standing on the goal
+                Some(next) if !map.blocked[next] => {
+                    pos.x = next as i32 % map.width;
+                    pos.y = next as i32 / map.width;
+                    // keep other monsters from stepping into the same tile
+                    map.blocked[here] = false;
+                    map.blocked[next] = true;
                     viewshed.dirty = true;
+                    if let Some(me) = creatures.iter_mut().find(|(other, _, _)| *other == entity) {
+                        me.1 = Point::new(pos.x, pos.y);
+                    }
+                    moved
+                        .insert(entity, EntityMoved {})
+                        .expect("Unable to insert marker.");
+                    if map.tiles[next] == TileType::ShallowWater {
+                        wading
+                            .insert(entity, Wading {})
+                            .expect("Unable to insert marker.");
+                    }
+                }
+                Some(_) => {}
+                None => {
+                    if noises.get(entity).is_some() {
+                        // either arrived or there's no way there
+                        done_investigating.push(entity);
+                    }
                 }
             }
         }
+
+        for entity in done_investigating {
+            noises.remove(entity);
+        }
+    }
+}
+
+/// Picks a spell for a monster with an enemy in sight, `distance` away:
+/// healing when badly hurt, otherwise something to throw at the enemy
+fn choose_spell(
+    stats: Option<&CombatStats>,
+    mana: Option<&Mana>,
+    known: Option<&KnownSpells>,
+    distance: f32,
+) -> Option<Spell> {
+    let (mana, known) = match (mana, known) {
+        (Some(mana), Some(known)) => (mana, known),
+        _ => return None,
+    };
+    let can_cast =
+        |spell: Spell| known.spells.contains(&spell) && mana.current >= spell.mana_cost();
+
+    let badly_hurt = stats.is_some_and(|stats| stats.hp < stats.max_hp / 2);
+    if badly_hurt && can_cast(Spell::Heal) {
+        return Some(Spell::Heal);
+    }
+    [Spell::MagicMissile, Spell::Confuse]
+        .iter()
+        .copied()
+        .find(|spell| can_cast(*spell) && spell.range().is_some_and(|r| distance <= r as f32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::factions::FactionId;
+    use crate::fov::FovAlgorithm;
+
+    fn shaman_spells() -> KnownSpells {
+        KnownSpells {
+            spells: vec![Spell::MagicMissile, Spell::Heal],
+        }
+    }
+
+    #[test]
+    fn heals_when_below_half_health() {
+        let hurt = CombatStats::new(20, 9, 0, 0);
+        let mana = Mana::new(10, 3);
+        let spell = choose_spell(Some(&hurt), Some(&mana), Some(&shaman_spells()), 2.0);
+        assert_eq!(spell, Some(Spell::Heal));
+
+        let fine = CombatStats::new(20, 10, 0, 0);
+        let spell = choose_spell(Some(&fine), Some(&mana), Some(&shaman_spells()), 2.0);
+        assert_eq!(spell, Some(Spell::MagicMissile));
+    }
+
+    #[test]
+    fn only_attacks_with_spells_in_range() {
+        let stats = CombatStats::new(20, 20, 0, 0);
+        let mana = Mana::new(10, 3);
+        let range = Spell::MagicMissile.range().unwrap() as f32;
+        let in_range = choose_spell(Some(&stats), Some(&mana), Some(&shaman_spells()), range);
+        assert_eq!(in_range, Some(Spell::MagicMissile));
+        let too_far = choose_spell(
+            Some(&stats),
+            Some(&mana),
+            Some(&shaman_spells()),
+            range + 1.0,
+        );
+        assert_eq!(too_far, None);
+    }
+
+    #[test]
+    fn never_picks_a_spell_it_cant_afford() {
+        let hurt = CombatStats::new(20, 1, 0, 0);
+        let mut mana = Mana::new(10, 3);
+        mana.current = Spell::Heal.mana_cost() - 1;
+        let spell = choose_spell(Some(&hurt), Some(&mana), Some(&shaman_spells()), 2.0);
+        // too poor to heal, but a missile is cheaper
+        assert_eq!(spell, Some(Spell::MagicMissile));
+
+        mana.current = Spell::MagicMissile.mana_cost() - 1;
+        let spell = choose_spell(Some(&hurt), Some(&mana), Some(&shaman_spells()), 2.0);
+        assert_eq!(spell, None);
+    }
+
+    #[test]
+    fn monsters_without_spells_dont_cast() {
+        let stats = CombatStats::new(20, 1, 0, 0);
+        assert_eq!(choose_spell(Some(&stats), None, None, 1.0), None);
+    }
+
+    /// A world with a corridor along y = 5, the player at (2, 5) and a wolf
+    /// on a leash of 3 at `wolf_x`
+    fn wolf_following_player(wolf_x: i32) -> (World, Entity) {
+        let mut ecs = World::new();
+        ecs.register::<Viewshed>();
+        ecs.register::<Monster>();
+        ecs.register::<Faction>();
+        ecs.register::<Position>();
+        ecs.register::<EntityMoved>();
+        ecs.register::<HeardNoise>();
+        ecs.register::<Confusion>();
+        ecs.register::<CombatStats>();
+        ecs.register::<Mana>();
+        ecs.register::<KnownSpells>();
+        ecs.register::<WantsToCastSpell>();
+        ecs.register::<CanMelee>();
+        ecs.register::<Follower>();
+        ecs.register::<Wading>();
+
+        let mut map = Map::new(12, 10);
+        for x in 1..11 {
+            map[(x, 5)] = TileType::Floor;
+        }
+        map.populate_blocked();
+        ecs.insert(map);
+        ecs.insert(Reactions::default());
+
+        let player = ecs
+            .create_entity()
+            .with(Position::new(2, 5))
+            .with(CombatStats::new(30, 30, 0, 0))
+            .with(Faction {
+                id: FactionId::Player,
+            })
+            .build();
+        let wolf = ecs
+            .create_entity()
+            .with(Position::new(wolf_x, 5))
+            .with(Viewshed {
+                visible_tiles: Vec::new(),
+                range: 8,
+                dirty: false,
+                algorithm: FovAlgorithm::SymmetricShadowcasting,
+            })
+            .with(Monster {})
+            .with(CombatStats::new(10, 10, 0, 0))
+            .with(Faction {
+                id: FactionId::Player,
+            })
+            .with(Follower {
+                leader: player,
+                leash: 3,
+            })
+            .build();
+        (ecs, wolf)
+    }
+
+    fn x_of(ecs: &World, entity: Entity) -> i32 {
+        ecs.read_storage::<Position>().get(entity).unwrap().x
+    }
+
+    #[test]
+    fn allies_beyond_the_leash_head_back_to_their_leader() {
+        let (ecs, wolf) = wolf_following_player(8);
+        MonsterAI {}.run_now(&ecs);
+        assert_eq!(x_of(&ecs, wolf), 7);
+        MonsterAI {}.run_now(&ecs);
+        assert_eq!(x_of(&ecs, wolf), 6);
+    }
+
+    #[test]
+    fn allies_within_the_leash_stay_put() {
+        let (ecs, wolf) = wolf_following_player(5);
+        MonsterAI {}.run_now(&ecs);
+        assert_eq!(x_of(&ecs, wolf), 5);
+    }
+
+    #[test]
+    fn shallow_water_costs_monsters_a_turn() {
+        let (ecs, wolf) = wolf_following_player(8);
+        ecs.write_resource::<Map>()[(7, 5)] = TileType::ShallowWater;
+        MonsterAI {}.run_now(&ecs);
+        assert_eq!(x_of(&ecs, wolf), 7);
+        MonsterAI {}.run_now(&ecs);
+        assert_eq!(x_of(&ecs, wolf), 7);
+        MonsterAI {}.run_now(&ecs);
+        assert_eq!(x_of(&ecs, wolf), 6);
     }
 }
@@ -0,0 +1,98 @@
+// Short-lived effects, like the flash of a hit. Particles are entities that
+// only last a few hundred milliseconds of real time, so they come and go
+// without any turns passing.
+
+use crate::components::{ParticleLifetime, Position, RenderOrder, Renderable};
+use rltk::{Rltk, RGB};
+use specs::prelude::*;
+
+/// A particle waiting to be made
+struct ParticleRequest {
+    x: i32,
+    y: i32,
+    fg: RGB,
+    glyph: u8,
+    lifetime_ms: f32,
+}
+
+/// Resource that collects the particles systems want, until
+/// `ParticleSpawnSystem` makes them
+#[derive(Default)]
+pub struct ParticleBuilder {
+    requests: Vec<ParticleRequest>,
+}
+
+impl ParticleBuilder {
+    pub fn request(&mut self, x: i32, y: i32, fg: RGB, glyph: u8, lifetime_ms: f32) {
+        self.requests.push(ParticleRequest {
+            x,
+            y,
+            fg,
+            glyph,
+            lifetime_ms,
+        });
+    }
+}
+
+pub struct ParticleSpawnSystem {}
+
+impl<'a> System<'a> for ParticleSpawnSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteExpect<'a, ParticleBuilder>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, Renderable>,
+        WriteStorage<'a, ParticleLifetime>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, mut builder, mut positions, mut renderables, mut lifetimes) = data;
+
+        for request in builder.requests.drain(..) {
+            let particle = entities.create();
+            positions
+                .insert(particle, Position::new(request.x, request.y))
+                .expect("Unable to insert position.");
+            renderables
+                .insert(
+                    particle,
+                    Renderable {
+                        glyph: request.glyph,
+                        fg: request.fg,
+                        bg: RGB::named(rltk::BLACK),
+                        render_order: RenderOrder::Particle,
+                    },
+                )
+                .expect("Unable to insert renderable.");
+            lifetimes
+                .insert(
+                    particle,
+                    ParticleLifetime {
+                        lifetime_ms: request.lifetime_ms,
+                    },
+                )
+                .expect("Unable to insert lifetime.");
+        }
+    }
+}
+
+/// Ages every particle by the time the last frame took, and removes the ones
+/// that have run out
+pub fn cull_dead_particles(ecs: &mut World, ctx: &Rltk) {
+    let mut dead: Vec<Entity> = Vec::new();
+    {
+        let entities = ecs.entities();
+        let mut lifetimes = ecs.write_storage::<ParticleLifetime>();
+        for (entity, lifetime) in (&entities, &mut lifetimes).join() {
+            lifetime.lifetime_ms -= ctx.frame_time_ms;
+            if lifetime.lifetime_ms < 0.0 {
+                dead.push(entity);
+            }
+        }
+    }
+
+    for particle in dead {
+        ecs.delete_entity(particle)
+            .expect("Unable to delete particle.");
+    }
+}
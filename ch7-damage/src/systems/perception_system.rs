@@ -0,0 +1,72 @@
+// Gives the player a chance to spot hidden things they can see
+
+use crate::components::{Hidden, Name, Player, Position, Searching};
+use crate::map::Map;
+use rltk::{console, DistanceAlg, Point, RandomNumberGenerator};
+use specs::prelude::*;
+
+// 1 in this many chance, per turn, of spotting each hidden thing in view
+const SPOT_CHANCE: i32 = 6;
+// searching makes it much more likely to spot things this close
+const SEARCH_CHANCE: i32 = 2;
+const SEARCH_RADIUS: f32 = 3.0;
+
+pub struct PerceptionSystem {}
+
+impl<'a> System<'a> for PerceptionSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        ReadExpect<'a, Map>,
+        ReadExpect<'a, Point>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        Entities<'a>,
+        WriteStorage<'a, Hidden>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, Player>,
+        WriteStorage<'a, Searching>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            map,
+            player_position,
+            mut rng,
+            entities,
+            mut hidden,
+            positions,
+            names,
+            players,
+            mut searching,
+        ) = data;
+
+        let player_searching = (&players, &searching).join().next().is_some();
+
+        // the map's visible tiles are the ones the player can see
+        let mut spotted: Vec<Entity> = Vec::new();
+        for (entity, _hidden, pos) in (&entities, &hidden, &positions).join() {
+            if !map.visible_tiles[map.idx(pos.x, pos.y)] {
+                continue;
+            }
+
+            let p = Point::new(pos.x, pos.y);
+            let nearby = DistanceAlg::Pythagoras.distance2d(p, *player_position) <= SEARCH_RADIUS;
+            let chance = if player_searching && nearby {
+                SEARCH_CHANCE
+            } else {
+                SPOT_CHANCE
+            };
+            if rng.roll_dice(1, chance) == 1 {
+                spotted.push(entity);
+            }
+        }
+        searching.clear();
+
+        for entity in spotted {
+            if let Some(name) = names.get(entity) {
+                console::log(format!("You spot a {}.", name.name));
+            }
+            hidden.remove(entity);
+        }
+    }
+}
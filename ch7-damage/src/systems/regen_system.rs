@@ -0,0 +1,86 @@
+// Slowly heals entities that regenerate, and refills mana, as turns go by
+
+use crate::components::{CombatStats, HealthRegen, HungerClock, Mana, Player};
+use crate::systems::awareness_system::VisibleMonsters;
+use specs::prelude::*;
+
+pub struct RegenSystem {}
+
+impl<'a> System<'a> for RegenSystem {
+    type SystemData = (
+        ReadExpect<'a, VisibleMonsters>,
+        Entities<'a>,
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, HungerClock>,
+        WriteStorage<'a, HealthRegen>,
+        WriteStorage<'a, CombatStats>,
+        WriteStorage<'a, Mana>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (visible, entities, players, hunger_clocks, mut regens, mut combat_stats, mut mana) =
+            data;
+
+        for (entity, regen, stats) in (&entities, &mut regens, &mut combat_stats).join() {
+            // nobody catches their breath with enemies in sight
+            if players.get(entity).is_some() && !visible.seen.is_empty() {
+                regen.counter = 0;
+                continue;
+            }
+            if stats.hp >= stats.max_hp {
+                regen.counter = 0;
+                continue;
+            }
+
+            // entities that don't need to eat are never hungry
+            regen.counter += hunger_clocks
+                .get(entity)
+                .map_or(1, |clock| clock.state.regen_per_turn());
+            if regen.counter >= regen.turns_per_hp {
+                regen.counter = 0;
+                stats.hp = i32::min(stats.max_hp, stats.hp + 1);
+            }
+        }
+
+        // mana comes back whatever else is going on
+        for mana in (&mut mana).join() {
+            if mana.current >= mana.max {
+                mana.counter = 0;
+                continue;
+            }
+            mana.counter += 1;
+            if mana.counter >= mana.turns_per_point {
+                mana.counter = 0;
+                mana.current += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mana_comes_back_a_point_every_few_turns() {
+        let mut ecs = World::new();
+        ecs.register::<Player>();
+        ecs.register::<HungerClock>();
+        ecs.register::<HealthRegen>();
+        ecs.register::<CombatStats>();
+        ecs.register::<Mana>();
+        ecs.insert(VisibleMonsters::default());
+
+        let mut mana = Mana::new(2, 3);
+        mana.current = 0;
+        let caster = ecs.create_entity().with(mana).build();
+
+        let current = |ecs: &World| ecs.read_storage::<Mana>().get(caster).unwrap().current;
+        let mut regen = RegenSystem {};
+        // one point every three turns, up to the maximum
+        for expected in &[0, 0, 1, 1, 1, 2, 2, 2, 2] {
+            regen.run_now(&ecs);
+            assert_eq!(current(&ecs), *expected);
+        }
+    }
+}
@@ -0,0 +1,207 @@
+// Casts the spells that entities chose this turn
+
+use crate::components::{
+    BlocksTile, CombatStats, Confusion, DamageType, Faction, Follower, Mana, MeleeDamage, Monster,
+    Name, Player, Position, RenderOrder, Renderable, SuffersDamage, Viewshed, WantsToCastSpell,
+};
+use crate::fov::FovAlgorithm;
+use crate::map::Map;
+use crate::spells::Spell;
+use crate::systems::particle_system::ParticleBuilder;
+use rltk::{console, DiceType, Point, RandomNumberGenerator, RGB};
+use specs::prelude::*;
+
+// how many turns a confuse spell lasts
+const CONFUSION_TURNS: i32 = 4;
+// how far a called wolf will stray from whoever called it
+const WOLF_LEASH: i32 = 3;
+
+pub struct SpellSystem {}
+
+impl<'a> System<'a> for SpellSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        WriteExpect<'a, Map>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        WriteExpect<'a, Point>,
+        WriteExpect<'a, ParticleBuilder>,
+        Entities<'a>,
+        WriteStorage<'a, WantsToCastSpell>,
+        WriteStorage<'a, Mana>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, Player>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, Viewshed>,
+        WriteStorage<'a, CombatStats>,
+        WriteStorage<'a, SuffersDamage>,
+        WriteStorage<'a, Confusion>,
+        ReadStorage<'a, Faction>,
+        Read<'a, LazyUpdate>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            mut map,
+            mut rng,
+            mut player_position,
+            mut particles,
+            entities,
+            mut wants_to_cast,
+            mut mana,
+            names,
+            players,
+            mut positions,
+            mut viewsheds,
+            mut combat_stats,
+            mut suffer_damage,
+            mut confusion,
+            factions,
+            lazy,
+        ) = data;
+
+        for (caster, cast, mana) in (&entities, &wants_to_cast, &mut mana).join() {
+            let caster_name = names
+                .get(caster)
+                .map_or("Something".to_string(), |n| n.name.clone());
+            let spell = cast.spell;
+            if mana.current < spell.mana_cost() {
+                console::log(format!("{} doesn't have enough mana.", caster_name));
+                continue;
+            }
+            mana.current -= spell.mana_cost();
+            console::log(format!("{} casts {}.", caster_name, spell.name()));
+
+            // anything that could be hurt, standing where the spell lands
+            let victims: Vec<Entity> = cast.target.map_or(Vec::new(), |target| {
+                map.tile_content[map.point_idx(target)]
+                    .iter()
+                    .copied()
+                    .filter(|entity| combat_stats.get(*entity).is_some())
+                    .collect()
+            });
+
+            match spell {
+                Spell::MagicMissile => {
+                    for victim in victims {
+                        let damage = rng.roll_dice(2, 6);
+                        SuffersDamage::new_damage(
+                            &mut suffer_damage,
+                            victim,
+                            damage,
+                            DamageType::Magic,
+                            Some(caster),
+                        );
+                    }
+                    if let Some(target) = cast.target {
+                        let colour = RGB::named(rltk::MAGENTA);
+                        particles.request(target.x, target.y, colour, rltk::to_cp437('*'), 300.0);
+                    }
+                }
+                Spell::Confuse => {
+                    for victim in victims {
+                        if let Some(name) = names.get(victim) {
+                            console::log(format!("{} is confused.", name.name));
+                        }
+                        confusion
+                            .insert(
+                                victim,
+                                Confusion {
+                                    turns: CONFUSION_TURNS,
+                                },
+                            )
+                            .expect("Unable to insert confusion.");
+                    }
+                    if let Some(target) = cast.target {
+                        let colour = RGB::named(rltk::MAGENTA);
+                        particles.request(target.x, target.y, colour, rltk::to_cp437('?'), 300.0);
+                    }
+                }
+                Spell::Heal => {
+                    if let Some(stats) = combat_stats.get_mut(caster) {
+                        stats.hp = i32::min(stats.max_hp, stats.hp + rng.roll_dice(2, 6) + 2);
+                    }
+                    if let Some(pos) = positions.get(caster) {
+                        let colour = RGB::named(rltk::GREEN);
+                        particles.request(pos.x, pos.y, colour, rltk::to_cp437('♥'), 300.0);
+                    }
+                }
+                Spell::CallWolf => {
+                    // the wolf turns up next to the caster, if there's room
+                    let spot = positions.get(caster).and_then(|pos| {
+                        map.neighbours(pos.x, pos.y)
+                            .map(|(p, _cost)| p)
+                            .find(|p| map.is_walkable(p.x, p.y) && !map.blocked[map.point_idx(*p)])
+                    });
+                    let (spot, faction) = match (spot, factions.get(caster)) {
+                        (Some(spot), Some(faction)) => (spot, *faction),
+                        _ => {
+                            console::log("Nothing answers the call.");
+                            continue;
+                        }
+                    };
+                    lazy.create_entity(&entities)
+                        .with(Position::new(spot.x, spot.y))
+                        .with(Renderable {
+                            glyph: rltk::to_cp437('w'),
+                            fg: RGB::named(rltk::LIGHT_BLUE),
+                            bg: RGB::named(rltk::BLACK),
+                            render_order: RenderOrder::Creature,
+                        })
+                        .with(Viewshed {
+                            visible_tiles: Vec::new(),
+                            range: 8,
+                            dirty: true,
+                            algorithm: FovAlgorithm::SymmetricShadowcasting,
+                        })
+                        .with(Monster {})
+                        .with(faction)
+                        .with(Follower {
+                            leader: caster,
+                            leash: WOLF_LEASH,
+                        })
+                        .with(Name {
+                            name: "Wolf".to_string(),
+                        })
+                        .with(CombatStats::new(12, 12, 1, 4))
+                        .with(MeleeDamage {
+                            dice: DiceType::new(1, 6, 0),
+                        })
+                        .with(BlocksTile {})
+                        .build();
+                    particles.request(
+                        spot.x,
+                        spot.y,
+                        RGB::named(rltk::LIGHT_BLUE),
+                        rltk::to_cp437('*'),
+                        300.0,
+                    );
+                }
+                Spell::Blink => {
+                    let destination = match map.random_floor_tile(&mut rng) {
+                        Some(destination) => destination,
+                        None => continue,
+                    };
+                    if let Some(pos) = positions.get_mut(caster) {
+                        let colour = RGB::named(rltk::CYAN);
+                        particles.request(pos.x, pos.y, colour, rltk::to_cp437('░'), 300.0);
+                        // the map isn't indexed again until next turn, so move the
+                        // caster's blocked tile along with them
+                        let (here, there) = (map.idx(pos.x, pos.y), map.point_idx(destination));
+                        map.blocked[here] = false;
+                        map.blocked[there] = true;
+                        pos.x = destination.x;
+                        pos.y = destination.y;
+                    }
+                    if let Some(viewshed) = viewsheds.get_mut(caster) {
+                        viewshed.dirty = true;
+                    }
+                    if players.get(caster).is_some() {
+                        *player_position = destination;
+                    }
+                }
+            }
+        }
+
+        wants_to_cast.clear();
+    }
+}
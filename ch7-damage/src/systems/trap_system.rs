@@ -0,0 +1,132 @@
+// Triggers traps when something moves onto them
+
+use crate::components::{
+    DamageType, EntityMoved, HeardNoise, Hidden, Monster, Name, Player, Position, SuffersDamage,
+    Trap, TrapKind, Viewshed,
+};
+use crate::map::Map;
+use rltk::{console, DistanceAlg, Point, RandomNumberGenerator};
+use specs::prelude::*;
+
+const SPIKE_DAMAGE: i32 = 6;
+// monsters this close to an alarm will come and look
+const ALARM_RADIUS: f32 = 20.0;
+
+pub struct TrapSystem {}
+
+impl<'a> System<'a> for TrapSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        WriteExpect<'a, Map>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        WriteExpect<'a, Point>,
+        Entities<'a>,
+        WriteStorage<'a, EntityMoved>,
+        WriteStorage<'a, Position>,
+        ReadStorage<'a, Trap>,
+        WriteStorage<'a, Hidden>,
+        ReadStorage<'a, Name>,
+        WriteStorage<'a, SuffersDamage>,
+        WriteStorage<'a, Viewshed>,
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, Monster>,
+        WriteStorage<'a, HeardNoise>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            mut map,
+            mut rng,
+            mut player_position,
+            entities,
+            mut moved,
+            mut positions,
+            traps,
+            mut hidden,
+            names,
+            mut suffer_damage,
+            mut viewsheds,
+            players,
+            monsters,
+            mut noises,
+        ) = data;
+
+        // find every trap that something stepped on this turn
+        let mut triggered: Vec<(Entity, Entity)> = Vec::new();
+        for (entity, _moved, pos) in (&entities, &moved, &positions).join() {
+            let i = map.idx(pos.x, pos.y);
+            for content in map.tile_content[i].iter() {
+                if *content != entity && traps.get(*content).is_some() {
+                    triggered.push((entity, *content));
+                }
+            }
+        }
+        moved.clear();
+
+        for (victim, trap_entity) in triggered {
+            let trap = traps.get(trap_entity).unwrap();
+            let victim_name = names
+                .get(victim)
+                .map_or("Something".to_string(), |n| n.name.clone());
+
+            // a triggered trap is no longer a secret
+            hidden.remove(trap_entity);
+
+            match trap.kind {
+                TrapKind::Spike => {
+                    console::log(format!(
+                        "{} steps on a spike trap, for {} hp.",
+                        victim_name, SPIKE_DAMAGE
+                    ));
+                    SuffersDamage::new_damage(
+                        &mut suffer_damage,
+                        victim,
+                        SPIKE_DAMAGE,
+                        DamageType::Piercing,
+                        Some(trap_entity),
+                    );
+                }
+                TrapKind::Teleport => {
+                    if let Some(destination) = map.random_floor_tile(&mut rng) {
+                        console::log(format!("{} is teleported away!", victim_name));
+                        if let Some(pos) = positions.get_mut(victim) {
+                            // the map was indexed before traps went off, so
+                            // the victim's blocked tile has to move with it
+                            let here = map.idx(pos.x, pos.y);
+                            let there = map.point_idx(destination);
+                            map.blocked[here] = false;
+                            map.blocked[there] = true;
+                            pos.x = destination.x;
+                            pos.y = destination.y;
+                        }
+                        if let Some(viewshed) = viewsheds.get_mut(victim) {
+                            viewshed.dirty = true;
+                        }
+                        if players.get(victim).is_some() {
+                            *player_position = destination;
+                        }
+                    }
+                }
+                TrapKind::Alarm => {
+                    console::log(format!("{} sets off an alarm!", victim_name));
+                    let at = positions.get(victim).map(|p| Point::new(p.x, p.y)).unwrap();
+                    for (monster, _m, pos) in (&entities, &monsters, &positions).join() {
+                        let distance =
+                            DistanceAlg::Pythagoras.distance2d(Point::new(pos.x, pos.y), at);
+                        if monster != victim && distance < ALARM_RADIUS {
+                            noises
+                                .insert(monster, HeardNoise { at })
+                                .expect("Unable to insert noise.");
+                        }
+                    }
+                }
+            }
+
+            if !trap.rearms {
+                entities
+                    .delete(trap_entity)
+                    .expect("Unable to delete trap.");
+            }
+        }
+    }
+}
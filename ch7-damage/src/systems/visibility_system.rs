@@ -2,7 +2,7 @@
 
 use crate::components::{Player, Position, Viewshed};
 use crate::map::Map;
-use rltk::{field_of_view, Point};
+use rltk::Point;
 use specs::prelude::*;
 
 pub struct VisibilitySystem {}
@@ -19,26 +19,202 @@ impl<'a> System<'a> for VisibilitySystem {
     fn run(&mut self, data: Self::SystemData) {
         let (mut map, entities, mut viewshed, pos, player) = data;
 
+        // if the map changed, everyone's view may have changed with it
+        let map_changed = map.view_changed;
+        map.view_changed = false;
+
         for (ent, viewshed, pos) in (&entities, &mut viewshed, &pos).join() {
-            viewshed.dirty = false; // don't need to update this tile, unless...
-            viewshed.visible_tiles.clear();
-            viewshed.visible_tiles = field_of_view(Point::new(pos.x, pos.y), viewshed.range, &*map);
-            viewshed
-                .visible_tiles
-                .retain(|p| p.x > 0 && p.x < map.width - 1 && p.y > 0 && p.y < map.height - 1);
-
-            // if this entity is the player, reveal what they can see
-            if let Some(_p) = player.get(ent) {
-                for t in map.visible_tiles.iter_mut() {
-                    *t = false
+            let recompute = viewshed.dirty || map_changed;
+            let is_player = player.get(ent).is_some();
+            let update_player = is_player && (recompute || map.light_changed);
+
+            // forget the tiles the player could see before, without having
+            // to clear the whole map
+            if update_player {
+                for visible in viewshed.visible_tiles.iter() {
+                    let i = map.point_idx(*visible);
+                    map.visible_tiles[i] = false;
                 }
+            }
+
+            // Viewsheds only hold line of sight. Whether a tile is lit is
+            // checked when the viewshed is used, since light changes more
+            // often than what an entity can see.
+            if recompute {
+                viewshed.dirty = false;
+                viewshed.visible_tiles = viewshed.algorithm.field_of_view(
+                    Point::new(pos.x, pos.y),
+                    viewshed.range,
+                    &map,
+                );
+            }
 
+            // if this entity is the player, reveal what they can see.
+            // Things in the dark can't be seen, even if they are in range.
+            if update_player {
                 for visible in viewshed.visible_tiles.iter() {
-                    let i = map.idx(visible.x, visible.y);
-                    map.revealed_tiles[i] = true;
-                    map.visible_tiles[i] = true;
+                    let i = map.point_idx(*visible);
+                    if map.is_lit(i) {
+                        map.revealed_tiles[i] = true;
+                        map.visible_tiles[i] = true;
+                    }
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fov::FovAlgorithm;
+    use crate::map::TileType;
+    use std::time::Instant;
+
+    // An open, lit room with a wall around the edge
+    fn open_map(width: i32, height: i32) -> Map {
+        let mut map = Map::new(width, height);
+        for y in 1..height - 1 {
+            for x in 1..width - 1 {
+                map[(x, y)] = TileType::Floor;
+            }
+        }
+        for i in 0..map.light.len() {
+            map.lit_tiles[i] = true;
+            map.light[i] = rltk::RGB::from_f32(1., 1., 1.);
+        }
+        map
+    }
+
+    fn test_world(map: Map) -> World {
+        let mut ecs = World::new();
+        ecs.register::<Position>();
+        ecs.register::<Viewshed>();
+        ecs.register::<Player>();
+        ecs.insert(map);
+        ecs
+    }
+
+    fn viewer(ecs: &mut World, x: i32, y: i32, dirty: bool) -> Entity {
+        ecs.create_entity()
+            .with(Position::new(x, y))
+            .with(Viewshed {
+                visible_tiles: vec![Point::new(x, y)],
+                range: 8,
+                dirty,
+                algorithm: FovAlgorithm::SymmetricShadowcasting,
+            })
+            .build()
+    }
+
+    #[test]
+    fn only_dirty_viewsheds_are_recomputed() {
+        let mut ecs = test_world(open_map(20, 20));
+        let clean = viewer(&mut ecs, 5, 5, false);
+        let dirty = viewer(&mut ecs, 10, 10, true);
+
+        VisibilitySystem {}.run_now(&ecs);
+
+        let viewsheds = ecs.read_storage::<Viewshed>();
+        assert_eq!(viewsheds.get(clean).unwrap().visible_tiles.len(), 1);
+        assert!(viewsheds.get(dirty).unwrap().visible_tiles.len() > 1);
+        assert!(!viewsheds.get(dirty).unwrap().dirty);
+    }
+
+    #[test]
+    fn map_changes_recompute_every_viewshed() {
+        let mut ecs = test_world(open_map(20, 20));
+        let clean = viewer(&mut ecs, 5, 5, false);
+        ecs.write_resource::<Map>().set_tile(7, 7, TileType::Wall);
+
+        VisibilitySystem {}.run_now(&ecs);
+
+        let viewsheds = ecs.read_storage::<Viewshed>();
+        assert!(viewsheds.get(clean).unwrap().visible_tiles.len() > 1);
+        assert!(!ecs.fetch::<Map>().view_changed);
+    }
+
+    #[test]
+    fn player_visible_tiles_follow_the_player() {
+        let mut ecs = test_world(open_map(40, 20));
+        let player = viewer(&mut ecs, 5, 10, true);
+        ecs.write_storage::<Player>()
+            .insert(player, Player {})
+            .unwrap();
+        VisibilitySystem {}.run_now(&ecs);
+
+        {
+            let map = ecs.fetch::<Map>();
+            assert!(map.visible_tiles[map.idx(5, 10)]);
+            assert!(!map.visible_tiles[map.idx(30, 10)]);
+        }
+
+        // move the player across the room
+        ecs.write_storage::<Position>().get_mut(player).unwrap().x = 30;
+        ecs.write_storage::<Viewshed>()
+            .get_mut(player)
+            .unwrap()
+            .dirty = true;
+        VisibilitySystem {}.run_now(&ecs);
+
+        let map = ecs.fetch::<Map>();
+        assert!(!map.visible_tiles[map.idx(5, 10)]);
+        assert!(map.visible_tiles[map.idx(30, 10)]);
+        assert!(map.revealed_tiles[map.idx(5, 10)]);
+    }
+
+    /// Compares the cost of a turn when every viewshed is recomputed with one
+    /// where only the player's is. Run with
+    /// `cargo test --release -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_visibility_with_100_monsters() {
+        const TURNS: u32 = 200;
+
+        let mut rng = rltk::RandomNumberGenerator::seeded(1);
+        let map = Map::new_map_rooms_and_corridors(80, 50, &mut rng);
+        let mut floor: Vec<(i32, i32)> = Vec::new();
+        for y in 0..map.height {
+            for x in 0..map.width {
+                if map[(x, y)] == TileType::Floor {
+                    floor.push((x, y));
+                }
+            }
+        }
+
+        let mut ecs = test_world(map);
+        let step = floor.len() / 101;
+        let (px, py) = floor[0];
+        let player = viewer(&mut ecs, px, py, true);
+        ecs.write_storage::<Player>()
+            .insert(player, Player {})
+            .unwrap();
+        for i in 1..=100 {
+            let (x, y) = floor[i * step];
+            viewer(&mut ecs, x, y, true);
+        }
+
+        let time_turns = |ecs: &mut World, all_dirty: bool| {
+            let start = Instant::now();
+            for _ in 0..TURNS {
+                {
+                    let mut viewsheds = ecs.write_storage::<Viewshed>();
+                    if all_dirty {
+                        for v in (&mut viewsheds).join() {
+                            v.dirty = true;
+                        }
+                    } else {
+                        viewsheds.get_mut(player).unwrap().dirty = true;
+                    }
+                }
+                VisibilitySystem {}.run_now(ecs);
+            }
+            start.elapsed() / TURNS
+        };
+
+        let full = time_turns(&mut ecs, true);
+        let incremental = time_turns(&mut ecs, false);
+        println!("every viewshed recomputed: {:?} per turn", full);
+        println!("only the player's recomputed: {:?} per turn", incremental);
+    }
+}
@@ -0,0 +1,107 @@
+// Buying and selling with vendors. Items are carried in backpacks, and move
+// from one backpack to the other when they are traded.
+
+use crate::components::{InBackpack, Purse, Value, Vendor};
+use specs::prelude::*;
+
+/// What a vendor with the given markup charges for an item worth `value`
+pub fn buy_price(value: i32, markup: i32) -> i32 {
+    value * (100 + markup) / 100
+}
+
+/// What a vendor with the given markup pays for an item worth `value`
+pub fn sell_price(value: i32, markup: i32) -> i32 {
+    value * 100 / (100 + markup)
+}
+
+fn value_of(ecs: &World, item: Entity) -> i32 {
+    ecs.read_storage::<Value>()
+        .get(item)
+        .map_or(0, |value| value.gold)
+}
+
+fn markup_of(ecs: &World, vendor: Entity) -> i32 {
+    ecs.read_storage::<Vendor>()
+        .get(vendor)
+        .map_or(0, |vendor| vendor.markup)
+}
+
+/// The price of an item, buying it from the vendor or selling it to them
+pub fn price(ecs: &World, vendor: Entity, item: Entity, selling: bool) -> i32 {
+    let (value, markup) = (value_of(ecs, item), markup_of(ecs, vendor));
+    if selling {
+        sell_price(value, markup)
+    } else {
+        buy_price(value, markup)
+    }
+}
+
+/// Moves `item` from the seller's backpack to the buyer's, and the price the
+/// other way. Fails if the buyer can't afford it.
+pub fn trade(
+    ecs: &mut World,
+    buyer: Entity,
+    seller: Entity,
+    item: Entity,
+    price: i32,
+) -> Result<(), &'static str> {
+    let mut purses = ecs.write_storage::<Purse>();
+    let buyer_gold = purses.get(buyer).map_or(0, |purse| purse.gold);
+    if buyer_gold < price {
+        return Err("can't afford that");
+    }
+
+    if let Some(purse) = purses.get_mut(buyer) {
+        purse.gold -= price;
+    }
+    match purses.get_mut(seller) {
+        Some(purse) => purse.gold += price,
+        None => {
+            purses
+                .insert(seller, Purse { gold: price })
+                .expect("Unable to insert purse.");
+        }
+    }
+    ecs.write_storage::<InBackpack>()
+        .insert(item, InBackpack { owner: buyer })
+        .expect("Unable to move item.");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vendors_buy_low_and_sell_high() {
+        assert_eq!(buy_price(10, 50), 15);
+        assert_eq!(sell_price(10, 50), 6);
+        assert_eq!(buy_price(10, 0), 10);
+        assert_eq!(sell_price(10, 0), 10);
+        for value in 1..100 {
+            assert!(sell_price(value, 25) <= buy_price(value, 25));
+        }
+    }
+
+    #[test]
+    fn trading_moves_the_item_and_the_gold() {
+        let mut ecs = World::new();
+        ecs.register::<Purse>();
+        ecs.register::<InBackpack>();
+        let buyer = ecs.create_entity().with(Purse { gold: 20 }).build();
+        let seller = ecs.create_entity().build();
+        let item = ecs
+            .create_entity()
+            .with(InBackpack { owner: seller })
+            .build();
+
+        assert!(trade(&mut ecs, buyer, seller, item, 30).is_err());
+        assert!(trade(&mut ecs, buyer, seller, item, 15).is_ok());
+
+        let purses = ecs.read_storage::<Purse>();
+        assert_eq!(purses.get(buyer).unwrap().gold, 5);
+        assert_eq!(purses.get(seller).unwrap().gold, 15);
+        let backpacks = ecs.read_storage::<InBackpack>();
+        assert_eq!(backpacks.get(item).unwrap().owner, buyer);
+    }
+}
@@ -0,0 +1,284 @@
+// Moving the player more than one step at a time: travelling to a tile that
+// was clicked on, and running in a direction
+
+use super::{RunState, State};
+use crate::interrupt::Snapshot;
+use crate::map::Map;
+use crate::player::try_move_player;
+use rltk::{console, Point, Rltk};
+use specs::prelude::*;
+
+/// Resource holding the rest of the path the player is travelling along
+#[derive(Default)]
+pub struct TravelPath {
+    pub steps: Vec<Point>,
+}
+
+/// Plans a path to the tile under the mouse. Returns `Travel` if there is a
+/// way there.
+pub fn start_travel(gs: &mut State, ctx: &Rltk) -> RunState {
+    let camera = gs.camera();
+    let (mx, my) = ctx.mouse_pos();
+    if mx >= camera.width || my >= camera.height {
+        // clicked on the sidebar
+        return RunState::Paused;
+    }
+    let (x, y) = camera.screen_to_world(mx, my);
+    let player_position = *gs.ecs.fetch::<Point>();
+
+    let steps = {
+        let map = gs.ecs.fetch::<Map>();
+        if !map.contains(x, y) || !map.revealed_tiles[map.idx(x, y)] || !map.is_walkable(x, y) {
+            return RunState::Paused;
+        }
+
+        let path = rltk::a_star_search(
+            map.point_idx(player_position) as i32,
+            map.idx(x, y) as i32,
+            &*map,
+        );
+        if !path.success {
+            console::log("You can't see a way there.");
+            return RunState::Paused;
+        }
+        path.steps
+            .iter()
+            .skip(1)
+            .map(|i| map.idx_point(*i))
+            .collect()
+    };
+
+    gs.ecs.write_resource::<TravelPath>().steps = steps;
+    RunState::Travel
+}
+
+/// Takes the next step along the travel path and runs the turn
+pub fn travel_turn(gs: &mut State) -> RunState {
+    let next = {
+        let mut path = gs.ecs.write_resource::<TravelPath>();
+        if path.steps.is_empty() {
+            return RunState::Paused;
+        }
+        path.steps.remove(0)
+    };
+
+    let here = *gs.ecs.fetch::<Point>();
+    let (dx, dy) = (next.x - here.x, next.y - here.y);
+    if dx.abs() > 1 || dy.abs() > 1 {
+        // the player was moved off the path, by a trap say
+        return RunState::Paused;
+    }
+
+    match take_step(gs, dx, dy) {
+        Some(reason) => {
+            console::log(format!("You stop: {}.", reason));
+            RunState::Paused
+        }
+        None if gs.ecs.fetch::<TravelPath>().steps.is_empty() => RunState::Paused,
+        None => RunState::Travel,
+    }
+}
+
+/// Runs a step in the direction (dx, dy), following corridors around
+/// corners. Stops at junctions, doorways and room entrances.
+pub fn run_turn(gs: &mut State, dx: i32, dy: i32) -> RunState {
+    let here = *gs.ecs.fetch::<Point>();
+    let ahead = Point::new(here.x + dx, here.y + dy);
+    if !gs.ecs.fetch::<Map>().is_walkable(ahead.x, ahead.y) {
+        return RunState::Paused;
+    }
+
+    if let Some(reason) = take_step(gs, dx, dy) {
+        console::log(format!("You stop running: {}.", reason));
+        return RunState::Paused;
+    }
+
+    let map = gs.ecs.fetch::<Map>();
+    let now = *gs.ecs.fetch::<Point>();
+    match next_run_direction(&map, here, now, (dx, dy)) {
+        Some((dx, dy)) => RunState::Run { dx, dy },
+        None => RunState::Paused,
+    }
+}
+
+/// Moves the player and runs the turn. Returns why the player should stop,
+/// if anything happened.
+fn take_step(gs: &mut State, dx: i32, dy: i32) -> Option<&'static str> {
+    let here = *gs.ecs.fetch::<Point>();
+    {
+        let map = gs.ecs.fetch::<Map>();
+        let ahead = map.idx(here.x + dx, here.y + dy);
+        if map.tiles[ahead].damage_on_enter().is_some() {
+            return Some("that looks dangerous");
+        }
+        if map.blocked[ahead] {
+            return Some("something is in the way");
+        }
+    }
+
+    let before = Snapshot::take(&gs.ecs);
+    try_move_player(dx, dy, &mut gs.ecs);
+    if *gs.ecs.fetch::<Point>() == here {
+        return Some("you can't go that way");
+    }
+    gs.run_systems();
+    before.interruption(&gs.ecs)
+}
+
+/// A tile is part of a corridor if there are only two ways out of it
+fn is_corridor(map: &Map, p: Point) -> bool {
+    exits(map, p).len() <= 2
+}
+
+/// A doorway is a gap in a wall: walls on opposite sides of it
+fn is_doorway(map: &Map, p: Point) -> bool {
+    let wall = |x: i32, y: i32| !map.is_walkable(x, y);
+    (wall(p.x - 1, p.y) && wall(p.x + 1, p.y)) || (wall(p.x, p.y - 1) && wall(p.x, p.y + 1))
+}
+
+/// The walkable neighbours of `p`, leaving out diagonals that can also be
+/// reached by a straight step first. Otherwise the tile before a bend in a
+/// corridor would have a third way out: the tile past the corner.
+fn exits(map: &Map, p: Point) -> Vec<Point> {
+    map.neighbours(p.x, p.y)
+        .map(|(n, _)| n)
+        .filter(|n| map.is_walkable(n.x, n.y))
+        .filter(|n| {
+            let diagonal = n.x != p.x && n.y != p.y;
+            !diagonal || !(map.is_walkable(n.x, p.y) || map.is_walkable(p.x, n.y))
+        })
+        .collect()
+}
+
+/// Works out which way to keep running after stepping from `previous` to
+/// `here` in `direction`, or None if this is somewhere to stop
+fn next_run_direction(
+    map: &Map,
+    previous: Point,
+    here: Point,
+    direction: (i32, i32),
+) -> Option<(i32, i32)> {
+    if is_corridor(map, here) {
+        // follow the corridor, unless it's a dead end
+        let ways_on: Vec<Point> = exits(map, here)
+            .into_iter()
+            .filter(|n| *n != previous)
+            .collect();
+        if ways_on.len() == 1 {
+            return Some((ways_on[0].x - here.x, ways_on[0].y - here.y));
+        }
+        return None;
+    }
+
+    // coming out of a corridor means reaching a junction or a room
+    if is_corridor(map, previous) || is_doorway(map, here) {
+        return None;
+    }
+
+    // in a room, keep going until something is in the way
+    let ahead = Point::new(here.x + direction.0, here.y + direction.1);
+    if map.is_walkable(ahead.x, ahead.y) {
+        Some(direction)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::TileType;
+
+    /// A map with floor on every given tile and wall everywhere else
+    fn map_with_floor(floor: &[(i32, i32)]) -> Map {
+        let mut map = Map::new(12, 12);
+        for &(x, y) in floor {
+            map[(x, y)] = TileType::Floor;
+        }
+        map
+    }
+
+    /// Runs from `start` in `direction` and returns where the player stops
+    fn run(map: &Map, start: (i32, i32), direction: (i32, i32)) -> Point {
+        let mut previous = Point::new(start.0, start.1);
+        let mut here = Point::new(start.0 + direction.0, start.1 + direction.1);
+        let mut direction = direction;
+        // long enough for any of these maps
+        for _ in 0..50 {
+            match next_run_direction(map, previous, here, direction) {
+                None => return here,
+                Some(next) => {
+                    direction = next;
+                    previous = here;
+                    here = Point::new(here.x + next.0, here.y + next.1);
+                }
+            }
+        }
+        panic!("never stopped running");
+    }
+
+    #[test]
+    fn runs_to_the_end_of_a_straight_corridor() {
+        let map = map_with_floor(&[(1, 5), (2, 5), (3, 5), (4, 5), (5, 5)]);
+        assert_eq!(run(&map, (1, 5), (1, 0)), Point::new(5, 5));
+    }
+
+    #[test]
+    fn follows_a_corridor_around_a_corner() {
+        // east along y = 5, then north up x = 5
+        let map = map_with_floor(&[
+            (1, 5),
+            (2, 5),
+            (3, 5),
+            (4, 5),
+            (5, 5),
+            (5, 4),
+            (5, 3),
+            (5, 2),
+        ]);
+        assert!(is_corridor(&map, Point::new(4, 5)));
+        assert_eq!(run(&map, (1, 5), (1, 0)), Point::new(5, 2));
+    }
+
+    #[test]
+    fn stops_at_a_junction() {
+        // east along y = 5, meeting a corridor running north to south
+        let map = map_with_floor(&[
+            (1, 5),
+            (2, 5),
+            (3, 5),
+            (4, 5),
+            (5, 3),
+            (5, 4),
+            (5, 5),
+            (5, 6),
+            (5, 7),
+        ]);
+        assert_eq!(run(&map, (1, 5), (1, 0)), Point::new(5, 5));
+    }
+
+    #[test]
+    fn stops_on_entering_a_room() {
+        // a corridor along y = 5 into a room from x = 5 to 9
+        let mut floor = vec![(1, 5), (2, 5), (3, 5), (4, 5)];
+        for x in 5..10 {
+            for y in 3..8 {
+                floor.push((x, y));
+            }
+        }
+        let map = map_with_floor(&floor);
+        assert_eq!(run(&map, (1, 5), (1, 0)), Point::new(5, 5));
+    }
+
+    #[test]
+    fn runs_across_a_room_until_something_is_in_the_way() {
+        let mut floor = Vec::new();
+        for x in 2..10 {
+            for y in 2..10 {
+                floor.push((x, y));
+            }
+        }
+        let map = map_with_floor(&floor);
+        assert_eq!(run(&map, (3, 5), (1, 0)), Point::new(9, 5));
+    }
+}